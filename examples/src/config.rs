@@ -5,7 +5,6 @@ use std::time::Duration;
 use nacos_rust_client::client::config_client::{ConfigClient, ConfigDefaultListener, ConfigKey};
 use nacos_rust_client::client::{AuthInfo, ClientBuilder, HostInfo};
 use serde::{Deserialize, Serialize};
-use serde_json;
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct Foo {
@@ -51,13 +50,8 @@ async fn check_listener_value() {
         number: 0u64,
     };
     let key = ConfigKey::new("foo_config", "foo", "");
-    let foo_config_obj_listener = Box::new(ConfigDefaultListener::new(
-        key.clone(),
-        Arc::new(|s| {
-            //字符串反序列化为对象，如:serde_json::from_str::<T>(s)
-            Some(serde_json::from_str::<Foo>(s).unwrap())
-        }),
-    ));
+    //按json格式解析为对象,解析失败时保留上一次的有效值
+    let foo_config_obj_listener = Box::new(ConfigDefaultListener::<Foo>::json(key.clone()));
     let foo_config_string_listener = Box::new(ConfigDefaultListener::new(
         key.clone(),
         Arc::new(|s| {
@@ -6,7 +6,6 @@ use nacos_rust_client::client::api_model::NamespaceInfo;
 use nacos_rust_client::client::config_client::api_model::ConfigQueryParams;
 use nacos_rust_client::client::{AuthInfo, ClientBuilder, ConfigClient, HostInfo};
 use serde::{Deserialize, Serialize};
-use serde_json;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
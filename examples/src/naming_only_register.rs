@@ -1,6 +1,5 @@
 #![allow(unused_imports, unreachable_code)]
 use actix::{Actor, Addr};
-use ctrlc;
 use nacos_rust_client::client::auth::AuthActor;
 use nacos_rust_client::client::naming_client::{InstanceDefaultListener, ServiceInstanceKey};
 use nacos_rust_client::conn_manage;
@@ -45,7 +44,7 @@ fn register(
 ) {
     log::info!("register,{},{}", service_name, group_name);
     for ip in ips {
-        let instance = Instance::new_simple(&ip, port, service_name, group_name);
+        let instance = Instance::new_simple(ip, port, service_name, group_name);
         //注册
         let msg = NamingRequest::Register(instance);
         conn_manage.do_send(msg);
@@ -63,8 +62,7 @@ where
         tx.send(addrs).unwrap();
         rt.run().unwrap();
     });
-    let addrs = rx.recv().unwrap();
-    addrs
+    rx.recv().unwrap()
 }
 
 /*
@@ -78,8 +76,7 @@ fn build_one_manage_addr(conn_manage:ConnManage) -> Addr<ConnManage> {
         tx.send(addrs).unwrap();
         rt.run().unwrap();
     });
-    let addrs = rx.recv().unwrap();
-    addrs
+    rx.recv().unwrap()
 }
 */
 
@@ -121,7 +118,7 @@ fn main() {
         let index = i % thread_num;
         let conn_manage_addr = &addrs.get(index).unwrap();
         let service_name = format!("foo_{}", i);
-        register(&service_name, group_name, &ips, 10000, &conn_manage_addr);
+        register(&service_name, group_name, &ips, 10000, conn_manage_addr);
     }
 
     println!("Waiting for Ctrl-C...");
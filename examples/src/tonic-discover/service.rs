@@ -54,7 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     for (ip, port) in &addrs {
-        let instance = Instance::new_simple(&ip, port.to_owned(), "helloworld", "AppName");
+        let instance = Instance::new_simple(ip, port.to_owned(), "helloworld", "AppName");
         client.register(instance);
     }
 
@@ -4,7 +4,7 @@ use nacos_rust_client::{init_global_system_actor, ActixSystemCreateCmd, ActorCre
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::RwLock;
 use tonic::transport::Channel;
 use tonic::transport::Endpoint;
 use tower::discover::Change;
@@ -223,16 +223,111 @@ impl ActorCreate for InnerTonicDiscoverCreate {
     }
 }
 
+/// 按名称管理多个 factory,同时记录最后创建的 factory
+#[derive(Default)]
+pub struct FactoryRegistry {
+    factories: RwLock<HashMap<String, Arc<TonicDiscoverFactory>>>,
+    last: RwLock<Option<Arc<TonicDiscoverFactory>>>,
+}
+
+impl FactoryRegistry {
+    pub fn register(&self, name: &str, factory: Arc<TonicDiscoverFactory>) {
+        let mut factories = self.factories.write().unwrap();
+        factories.insert(name.to_owned(), factory);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<TonicDiscoverFactory>> {
+        self.factories.read().unwrap().get(name).cloned()
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Arc<TonicDiscoverFactory>> {
+        self.factories.write().unwrap().remove(name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.factories.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn get_last(&self) -> Option<Arc<TonicDiscoverFactory>> {
+        self.last.read().unwrap().clone()
+    }
+
+    pub(crate) fn set_last(&self, factory: Arc<TonicDiscoverFactory>) {
+        *self.last.write().unwrap() = Some(factory);
+    }
+}
+
 lazy_static::lazy_static! {
-    static ref LAST_FACTORY: Mutex<Option<Arc<TonicDiscoverFactory>>> =  Mutex::new(None);
+    static ref FACTORY_REGISTRY: FactoryRegistry = FactoryRegistry::default();
 }
 
+/// 全局 factory 注册表
+pub fn factory_registry() -> &'static FactoryRegistry {
+    &FACTORY_REGISTRY
+}
+
+/// 最后创建的 factory
 pub fn get_last_factory() -> Option<Arc<TonicDiscoverFactory>> {
-    let r = LAST_FACTORY.lock().unwrap();
-    r.clone()
+    factory_registry().get_last()
+}
+
+pub(crate) fn set_last_factory(factory: Arc<TonicDiscoverFactory>) {
+    factory_registry().set_last(factory);
+}
+
+/// 按名称注册 factory,同时使用多个 NamingClient 时用名称区分
+pub fn register_factory(name: &str, factory: Arc<TonicDiscoverFactory>) {
+    factory_registry().register(name, factory);
+}
+
+/// 按名称获取 factory
+pub fn get_factory(name: &str) -> Option<Arc<TonicDiscoverFactory>> {
+    factory_registry().get(name)
 }
 
-pub(crate) fn set_last_factory(addr: Arc<TonicDiscoverFactory>) {
-    let mut r = LAST_FACTORY.lock().unwrap();
-    *r = Some(addr);
+/// 按名称移除 factory,返回被移除的 factory
+pub fn remove_factory(name: &str) -> Option<Arc<TonicDiscoverFactory>> {
+    factory_registry().remove(name)
+}
+
+/// 已注册的全部名称
+pub fn factory_names() -> Vec<String> {
+    factory_registry().names()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nacos_rust_client::client::HostInfo;
+
+    #[test]
+    fn test_factory_registry() {
+        let naming_client = NamingClient::new(HostInfo::parse("127.0.0.1:18848"), "".to_owned());
+        let factory = TonicDiscoverFactory::new(naming_client);
+        let registry = FactoryRegistry::default();
+        assert!(registry.get_last().is_none());
+        registry.set_last(factory.clone());
+        assert!(Arc::ptr_eq(&registry.get_last().unwrap(), &factory));
+
+        registry.register("factory_a", factory.clone());
+        registry.register("factory_b", factory.clone());
+        assert!(Arc::ptr_eq(&registry.get("factory_a").unwrap(), &factory));
+        let mut names = registry.names();
+        names.sort();
+        assert_eq!(names, vec!["factory_a", "factory_b"]);
+        assert!(registry.remove("factory_b").is_some());
+        assert!(registry.get("factory_b").is_none());
+        assert_eq!(registry.names(), vec!["factory_a"]);
+
+        //全局注册表
+        assert!(get_last_factory().is_some());
+        register_factory("test_factory_registry", factory.clone());
+        assert!(factory_names().contains(&"test_factory_registry".to_owned()));
+        assert!(Arc::ptr_eq(
+            &get_factory("test_factory_registry").unwrap(),
+            &factory
+        ));
+        assert!(remove_factory("test_factory_registry").is_some());
+        assert!(get_factory("test_factory_registry").is_none());
+    }
 }
@@ -0,0 +1,83 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use super::{utils::Utils, HostInfo};
+use crate::error::Error;
+
+/// 地址服务器(endpoint 模式),定时从地址服务器拉取 nacos 集群的服务地址列表。
+///
+/// 地址服务器返回的内容每行一个地址,格式与 `HostInfo::parse` 一致,如 `10.0.0.1:8848`。
+#[derive(Debug, Clone)]
+pub struct AddressServer {
+    url: String,
+    refresh_interval: Duration,
+    //最近一次拉取到的地址列表,多个客户端共享
+    hosts: Arc<RwLock<Vec<HostInfo>>>,
+}
+
+impl AddressServer {
+    /// url 为地址列表的完整地址,如 `http://endpoint:8080/nacos/serverlist`
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            refresh_interval: Duration::from_secs(30),
+            hosts: Default::default(),
+        }
+    }
+
+    pub fn set_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        self.refresh_interval
+    }
+
+    /// 最近一次拉取到的地址列表;还未拉取成功时为空
+    pub fn hosts(&self) -> Vec<HostInfo> {
+        self.hosts.read().unwrap().clone()
+    }
+
+    /// 从地址服务器拉取地址列表;返回空列表时视为失败,保留之前的列表
+    pub(crate) async fn fetch(&self, client: &reqwest::Client) -> anyhow::Result<Vec<HostInfo>> {
+        let resp = Utils::request(client, "GET", &self.url, vec![], None, Some(3000)).await?;
+        if !resp.status_is_200() {
+            return Err(Error::from_http_status(resp.status, resp.get_lossy_string_body()).into());
+        }
+        let hosts = Self::parse_server_list(&resp.get_lossy_string_body());
+        if hosts.is_empty() {
+            return Err(anyhow::anyhow!("address server return empty server list"));
+        }
+        *self.hosts.write().unwrap() = hosts.clone();
+        Ok(hosts)
+    }
+
+    fn parse_server_list(content: &str) -> Vec<HostInfo> {
+        content
+            .lines()
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty() && !e.starts_with('#'))
+            .map(HostInfo::parse)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server_list() {
+        let hosts =
+            AddressServer::parse_server_list("10.0.0.1:8848\r\n\n 10.0.0.2:8849#9849\n10.0.0.3\n");
+        assert_eq!(hosts.len(), 3);
+        assert_eq!(hosts[0].ip, "10.0.0.1");
+        assert_eq!(hosts[1].grpc_port, 9849);
+        assert_eq!(hosts[2].port, 8848);
+    }
+}
@@ -1,68 +1,203 @@
 use crate::client::ServerEndpointInfo;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
-use super::AuthInfo;
+use super::{AuthInfo, AuthProvider, AuthResource, HttpClients};
 use crate::client::nacos_client::{ActixSystemCmd, ActixSystemResult};
+use crate::error::Error;
 use crate::init_global_system_actor;
 use actix::{prelude::*, Context};
 
+/// 鉴权状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthState {
+    /// 登录成功
+    Authenticated,
+    /// 登录失败,会按 `ConnectionPolicy::reconnect_backoff` 重试
+    Failed(String),
+    /// token 过期或被服务端拒绝,正在重新登录
+    Expired,
+}
+
+/// 鉴权状态变化回调,通过 `ClientBuilder::set_auth_state_listener` 设置
+#[derive(Clone)]
+pub struct AuthStateListener(Arc<dyn Fn(AuthState) + Send + Sync>);
+
+impl AuthStateListener {
+    pub fn new(f: impl Fn(AuthState) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn notify(&self, state: AuthState) {
+        (self.0)(state)
+    }
+}
+
+impl std::fmt::Debug for AuthStateListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AuthStateListener")
+    }
+}
+
+//服务端拒绝 token 后重新登录的最小间隔,避免并发请求重复登录
+const INVALIDATE_INTERVAL_MILLIS: u64 = 1000;
+
 pub struct AuthActor {
     endpoints: Arc<ServerEndpointInfo>,
     auth: Option<AuthInfo>,
-    client: reqwest::Client,
+    //未直接设置用户名密码,登录时从鉴权方式读取
+    auth_from_provider: bool,
+    provider: Option<Arc<dyn AuthProvider>>,
+    clients: HttpClients,
     use_auth: bool,
     token: Arc<String>,
     token_time_out: u64,
+    login_time: u64,
+    state: Option<AuthState>,
+    //连续登录失败次数,用于计算重试等待时间
+    login_fail_times: u32,
+    login_retrying: bool,
 }
 
 impl AuthActor {
     pub fn new(endpoints: Arc<ServerEndpointInfo>, auth_info: Option<AuthInfo>) -> Self {
+        let provider = endpoints.auth_provider.clone();
+        //未设置用户名密码时使用鉴权方式中的登录信息
+        let auth_info = auth_info.filter(AuthInfo::is_valid);
+        let auth_from_provider = auth_info.is_none();
+        let auth_info = auth_info.or_else(|| provider.as_ref().and_then(|e| e.login_info()));
         let use_auth = if let Some(auth) = &auth_info {
             auth.is_valid()
         } else {
             false
         };
+        let clients = HttpClients::new(&endpoints);
         Self {
             endpoints,
             auth: auth_info,
-            client: reqwest::Client::new(),
+            auth_from_provider,
+            provider,
+            clients,
             use_auth,
             token: Default::default(),
             token_time_out: Default::default(),
+            login_time: 0,
+            state: None,
+            login_fail_times: 0,
+            login_retrying: false,
+        }
+    }
+
+    fn set_state(&mut self, state: AuthState) {
+        if self.state.as_ref() == Some(&state) {
+            return;
+        }
+        log::info!("AuthActor state change to {:?}", &state);
+        self.state = Some(state.clone());
+        if let Some(listener) = &self.endpoints.auth_state_listener {
+            listener.notify(state);
         }
     }
 
+    //token 有效期很短时不再提前 5 秒刷新
+    fn token_refresh_millis(token_ttl: u64) -> u64 {
+        (token_ttl - (token_ttl / 2).min(5)) * 1000
+    }
+
+    /// 从鉴权方式重新读取登录信息,凭证可能在客户端启动后才写入或被轮换;返回是否需要登录
+    fn refresh_auth_info(&mut self) -> bool {
+        if self.auth_from_provider {
+            if let Some(auth) = self
+                .provider
+                .as_ref()
+                .and_then(|e| e.login_info())
+                .filter(AuthInfo::is_valid)
+            {
+                self.auth = Some(auth);
+            }
+        }
+        self.use_auth = self.auth.as_ref().map(AuthInfo::is_valid).unwrap_or(false);
+        self.use_auth
+    }
+
     fn update_token(&mut self, ctx: &mut Context<Self>) {
-        if !self.use_auth {
+        //每次登录重新读取,以便使用轮换后的凭证
+        if !self.refresh_auth_info() {
             return;
         }
-        let client = self.client.clone();
+        let host = self.endpoints.select_host();
+        let client = self.clients.get(&self.endpoints, &host);
         let endpoints = self.endpoints.clone();
         let auth = self.auth.clone();
         async move {
             let auth = auth.unwrap();
-            super::Client::login(&client, endpoints, &auth).await
+            super::Client::login(&client, endpoints, &host, &auth).await
         }
         .into_actor(self)
-        .map(|result, this, _| {
-            if let Ok(token_info) = result {
+        .map(|result, this, ctx| match result {
+            Ok(token_info) => {
+                let now = super::now_millis();
                 this.token = Arc::new(token_info.access_token);
-                this.token_time_out = super::now_millis() + (token_info.token_ttl - 5) * 1000;
+                this.token_time_out = now + Self::token_refresh_millis(token_info.token_ttl);
+                this.login_time = now;
+                this.login_fail_times = 0;
+                this.set_state(AuthState::Authenticated);
+            }
+            Err(err) => {
+                log::error!("AuthActor login error,{}", err);
+                this.set_state(AuthState::Failed(err.to_string()));
+                this.retry_login(ctx);
             }
         })
         .wait(ctx);
     }
 
+    fn retry_login(&mut self, ctx: &mut Context<Self>) {
+        if self.login_retrying {
+            return;
+        }
+        self.login_retrying = true;
+        let delay = self
+            .endpoints
+            .policy
+            .reconnect_backoff
+            .delay(self.login_fail_times);
+        self.login_fail_times = self.login_fail_times.saturating_add(1);
+        ctx.run_later(delay, |act, ctx| {
+            act.login_retrying = false;
+            act.update_token(ctx);
+        });
+    }
+
+    /// 服务端拒绝当前 token 时重新登录
+    fn invalidate_token(&mut self, ctx: &mut Context<Self>) {
+        if !self.use_auth
+            || self.login_retrying
+            || super::now_millis() < self.login_time + INVALIDATE_INTERVAL_MILLIS
+        {
+            return;
+        }
+        self.token_time_out = 0;
+        self.set_state(AuthState::Expired);
+        self.update_token(ctx);
+    }
+
     fn get_token(&mut self, ctx: &mut Context<Self>) -> Arc<String> {
-        if !self.use_auth {
+        if !self.use_auth && !self.refresh_auth_info() {
             return Default::default();
         }
         let now = super::now_millis();
         if now < self.token_time_out {
             return self.token.clone();
         }
-        self.update_token(ctx);
+        //登录失败等待重试时不重复登录
+        if !self.login_retrying {
+            if !self.token.is_empty() {
+                self.set_state(AuthState::Expired);
+            }
+            self.update_token(ctx);
+        }
         if self.token.is_empty() {
             log::warn!("get token is empty");
         }
@@ -70,11 +205,17 @@ impl AuthActor {
     }
 
     pub fn hb(&mut self, ctx: &mut Context<Self>) {
-        if !self.use_auth {
+        if !self.use_auth && !self.refresh_auth_info() {
+            //登录信息来自鉴权方式时继续检查
+            if self.auth_from_provider && self.provider.is_some() {
+                ctx.run_later(Duration::from_secs(30), |act, ctx| {
+                    act.hb(ctx);
+                });
+            }
             return;
         }
         let now = super::now_millis();
-        if now + 60 * 1000 > self.token_time_out {
+        if now + 60 * 1000 > self.token_time_out && !self.login_retrying {
             self.update_token(ctx);
         }
         ctx.run_later(Duration::from_secs(30), |act, ctx| {
@@ -117,11 +258,16 @@ impl Actor for AuthActor {
 pub enum AuthCmd {
     //QueryToken(AuthHandleResultSender),
     QueryToken,
+    /// 查询请求需要附加的鉴权头部
+    QueryHeaders(AuthResource),
+    /// 请求返回 403 或鉴权错误,重新登录
+    Invalidate,
 }
 
 pub enum AuthHandleResult {
     None,
     Token(Arc<String>),
+    Headers(HashMap<String, String>),
 }
 
 impl Handler<AuthCmd> for AuthActor {
@@ -130,20 +276,53 @@ impl Handler<AuthCmd> for AuthActor {
         match msg {
             AuthCmd::QueryToken => {
                 let token = self.get_token(ctx);
+                if self.use_auth && token.is_empty() {
+                    //未登录成功时不发送请求
+                    return Ok(AuthHandleResult::None);
+                }
                 Ok(AuthHandleResult::Token(token))
             }
+            AuthCmd::Invalidate => {
+                self.invalidate_token(ctx);
+                Ok(AuthHandleResult::None)
+            }
+            AuthCmd::QueryHeaders(resource) => {
+                let headers = self
+                    .provider
+                    .as_ref()
+                    .map(|e| e.request_headers(&resource))
+                    .unwrap_or_default();
+                Ok(AuthHandleResult::Headers(headers))
+            }
         }
     }
 }
 
 pub async fn get_token_result(auth_addr: &Addr<AuthActor>) -> anyhow::Result<Arc<String>> {
     match auth_addr.send(AuthCmd::QueryToken).await?? {
-        AuthHandleResult::None => {}
-        AuthHandleResult::Token(v) => {
-            if v.len() > 0 {
-                return Ok(v);
-            }
-        }
-    };
-    Ok(Default::default())
+        AuthHandleResult::Token(v) => Ok(v),
+        _ => Err(Error::Unauthorized("login failed, access token is empty".to_owned()).into()),
+    }
+}
+
+pub async fn get_auth_headers(
+    auth_addr: &Addr<AuthActor>,
+    resource: AuthResource,
+) -> anyhow::Result<HashMap<String, String>> {
+    match auth_addr.send(AuthCmd::QueryHeaders(resource)).await?? {
+        AuthHandleResult::Headers(headers) => Ok(headers),
+        _ => Ok(Default::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_refresh_millis() {
+        assert_eq!(AuthActor::token_refresh_millis(18000), 17995 * 1000);
+        assert_eq!(AuthActor::token_refresh_millis(3), 2 * 1000);
+        assert_eq!(AuthActor::token_refresh_millis(0), 0);
+    }
 }
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use super::{now_millis, AuthInfo};
+
+/// 请求涉及的资源,用于计算签名
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AuthResource {
+    #[default]
+    None,
+    Config {
+        tenant: String,
+        group: String,
+    },
+    Naming {
+        namespace_id: String,
+        group_name: String,
+        service_name: String,
+    },
+}
+
+impl AuthResource {
+    pub fn config(tenant: &str, group: &str) -> Self {
+        Self::Config {
+            tenant: tenant.to_owned(),
+            group: group.to_owned(),
+        }
+    }
+
+    pub fn naming(namespace_id: &str, group_name: &str, service_name: &str) -> Self {
+        Self::Naming {
+            namespace_id: namespace_id.to_owned(),
+            group_name: group_name.to_owned(),
+            service_name: service_name.to_owned(),
+        }
+    }
+}
+
+/// 鉴权方式;http 与 gRPC 请求发送前都会调用 `request_headers` 获取需要附加的头部
+pub trait AuthProvider: Send + Sync + Debug {
+    /// 需要先登录获取 accessToken 时返回用户名密码
+    fn login_info(&self) -> Option<AuthInfo> {
+        None
+    }
+
+    /// 单个请求附加的鉴权头部
+    fn request_headers(&self, resource: &AuthResource) -> HashMap<String, String>;
+}
+
+/// 用户名密码鉴权:登录获取 accessToken,请求时携带 token
+impl AuthProvider for AuthInfo {
+    fn login_info(&self) -> Option<AuthInfo> {
+        if self.is_valid() {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+
+    fn request_headers(&self, _resource: &AuthResource) -> HashMap<String, String> {
+        HashMap::new()
+    }
+}
+
+/// 阿里云 AccessKey/SecretKey 签名鉴权(如 MSE nacos)。
+///
+/// 配置请求对 `tenant+group+timestamp` 签名,服务请求对 `timestamp@@group@@service` 签名,
+/// 签名算法为 HmacSHA1 后 base64。
+#[derive(Clone)]
+pub struct AccessKeyAuthProvider {
+    access_key: String,
+    secret_key: String,
+}
+
+impl AccessKeyAuthProvider {
+    pub fn new(access_key: &str, secret_key: &str) -> Self {
+        Self {
+            access_key: access_key.to_owned(),
+            secret_key: secret_key.to_owned(),
+        }
+    }
+
+    /// 从环境变量 `NACOS_ACCESS_KEY`、`NACOS_SECRET_KEY` 读取,未设置时返回 None
+    pub fn from_env() -> Option<Self> {
+        let access_key = std::env::var("NACOS_ACCESS_KEY").unwrap_or_default();
+        let secret_key = std::env::var("NACOS_SECRET_KEY").unwrap_or_default();
+        if access_key.is_empty() || secret_key.is_empty() {
+            return None;
+        }
+        Some(Self::new(&access_key, &secret_key))
+    }
+
+    pub fn access_key(&self) -> &str {
+        &self.access_key
+    }
+
+    pub fn sign(&self, data: &str) -> String {
+        let mut mac = Hmac::<Sha1>::new_from_slice(self.secret_key.as_bytes())
+            .expect("hmac accepts keys of any size");
+        mac.update(data.as_bytes());
+        base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+    }
+
+    fn headers_at(&self, resource: &AuthResource, timestamp: u64) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        match resource {
+            AuthResource::Config { tenant, group } => {
+                let sign_data = if !tenant.is_empty() && !group.is_empty() {
+                    format!("{}+{}+{}", tenant, group, timestamp)
+                } else if !group.is_empty() {
+                    format!("{}+{}", group, timestamp)
+                } else {
+                    timestamp.to_string()
+                };
+                headers.insert("Spas-AccessKey".to_owned(), self.access_key.clone());
+                headers.insert("Timestamp".to_owned(), timestamp.to_string());
+                headers.insert("Spas-Signature".to_owned(), self.sign(&sign_data));
+            }
+            AuthResource::Naming {
+                group_name,
+                service_name,
+                ..
+            } => {
+                let sign_data = if service_name.is_empty() {
+                    timestamp.to_string()
+                } else if group_name.is_empty() {
+                    format!("{}@@{}", timestamp, service_name)
+                } else {
+                    format!("{}@@{}@@{}", timestamp, group_name, service_name)
+                };
+                headers.insert("ak".to_owned(), self.access_key.clone());
+                headers.insert("signature".to_owned(), self.sign(&sign_data));
+                headers.insert("data".to_owned(), sign_data);
+            }
+            AuthResource::None => {}
+        }
+        headers
+    }
+}
+
+impl Debug for AccessKeyAuthProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessKeyAuthProvider")
+            .field("access_key", &self.access_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuthProvider for AccessKeyAuthProvider {
+    fn request_headers(&self, resource: &AuthResource) -> HashMap<String, String> {
+        self.headers_at(resource, now_millis())
+    }
+}
+
+const USERNAME_FILE: &str = "username";
+const PASSWORD_FILE: &str = "password";
+const ACCESS_KEY_FILE: &str = "accessKey";
+const SECRET_KEY_FILE: &str = "secretKey";
+
+#[derive(Debug, Default)]
+struct FileCredentials {
+    //各文件的修改时间与长度,变化时重新读取
+    fingerprint: Vec<Option<(SystemTime, u64)>>,
+    auth_info: Option<AuthInfo>,
+    access_key: Option<AccessKeyAuthProvider>,
+}
+
+/// 从目录中的文件读取凭证,如 Kubernetes 挂载的 secret。
+///
+/// 目录下可以包含 `username`、`password`、`accessKey`、`secretKey` 文件;
+/// 文件内容变化后重新读取,轮换后的用户名密码在下次登录时生效,AccessKey 在下个请求生效。
+#[derive(Debug)]
+pub struct FileAuthProvider {
+    dir: PathBuf,
+    credentials: Mutex<FileCredentials>,
+}
+
+impl FileAuthProvider {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            credentials: Default::default(),
+        }
+    }
+
+    fn fingerprint(&self) -> Vec<Option<(SystemTime, u64)>> {
+        [
+            USERNAME_FILE,
+            PASSWORD_FILE,
+            ACCESS_KEY_FILE,
+            SECRET_KEY_FILE,
+        ]
+        .iter()
+        .map(|name| {
+            std::fs::metadata(self.dir.join(name))
+                .and_then(|e| Ok((e.modified()?, e.len())))
+                .ok()
+        })
+        .collect()
+    }
+
+    fn read_file(dir: &Path, name: &str) -> String {
+        std::fs::read_to_string(dir.join(name))
+            .map(|e| e.trim().to_owned())
+            .unwrap_or_default()
+    }
+
+    fn with_credentials<T>(&self, f: impl FnOnce(&FileCredentials) -> T) -> T {
+        let fingerprint = self.fingerprint();
+        let mut credentials = self.credentials.lock().unwrap();
+        if credentials.fingerprint != fingerprint {
+            log::info!("reload credentials from {}", self.dir.display());
+            let auth_info = AuthInfo::new(
+                &Self::read_file(&self.dir, USERNAME_FILE),
+                &Self::read_file(&self.dir, PASSWORD_FILE),
+            );
+            let access_key = Self::read_file(&self.dir, ACCESS_KEY_FILE);
+            let secret_key = Self::read_file(&self.dir, SECRET_KEY_FILE);
+            *credentials = FileCredentials {
+                fingerprint,
+                auth_info: Some(auth_info).filter(AuthInfo::is_valid),
+                access_key: if access_key.is_empty() || secret_key.is_empty() {
+                    None
+                } else {
+                    Some(AccessKeyAuthProvider::new(&access_key, &secret_key))
+                },
+            };
+        }
+        f(&credentials)
+    }
+}
+
+impl AuthProvider for FileAuthProvider {
+    fn login_info(&self) -> Option<AuthInfo> {
+        self.with_credentials(|e| e.auth_info.clone())
+    }
+
+    fn request_headers(&self, resource: &AuthResource) -> HashMap<String, String> {
+        self.with_credentials(|e| {
+            e.access_key
+                .as_ref()
+                .map(|e| e.request_headers(resource))
+                .unwrap_or_default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_key_sign() {
+        let provider = AccessKeyAuthProvider::new("ak", "key");
+        assert_eq!(
+            provider.sign("The quick brown fox jumps over the lazy dog"),
+            "3nybhbi3iqa8ino29wqQcBydtNk="
+        );
+
+        let headers = provider.headers_at(&AuthResource::config("dev", "DEFAULT_GROUP"), 1000);
+        assert_eq!(headers.get("Spas-AccessKey").unwrap(), "ak");
+        assert_eq!(headers.get("Timestamp").unwrap(), "1000");
+        assert_eq!(
+            headers.get("Spas-Signature").unwrap(),
+            &provider.sign("dev+DEFAULT_GROUP+1000")
+        );
+
+        let headers = provider.headers_at(&AuthResource::naming("", "DEFAULT_GROUP", "foo"), 1000);
+        assert_eq!(headers.get("data").unwrap(), "1000@@DEFAULT_GROUP@@foo");
+        assert_eq!(
+            headers.get("signature").unwrap(),
+            &provider.sign("1000@@DEFAULT_GROUP@@foo")
+        );
+        assert!(provider.headers_at(&AuthResource::None, 1000).is_empty());
+        assert!(!format!("{:?}", provider).contains("\"key\""));
+    }
+
+    #[test]
+    fn test_file_auth_provider() {
+        let dir = std::env::temp_dir().join(format!("nacos_auth_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let provider = FileAuthProvider::new(&dir);
+        assert!(provider.login_info().is_none());
+
+        std::fs::write(dir.join(USERNAME_FILE), "nacos\n").unwrap();
+        std::fs::write(dir.join(PASSWORD_FILE), "v1").unwrap();
+        assert_eq!(provider.login_info().unwrap().password, "v1");
+        assert!(provider
+            .request_headers(&AuthResource::config("", "DEFAULT_GROUP"))
+            .is_empty());
+
+        //轮换后重新读取
+        std::fs::write(dir.join(PASSWORD_FILE), "rotated").unwrap();
+        std::fs::write(dir.join(ACCESS_KEY_FILE), "ak").unwrap();
+        std::fs::write(dir.join(SECRET_KEY_FILE), "sk").unwrap();
+        let auth_info = provider.login_info().unwrap();
+        assert_eq!(auth_info.username, "nacos");
+        assert_eq!(auth_info.password, "rotated");
+        let headers = provider.request_headers(&AuthResource::config("", "DEFAULT_GROUP"));
+        assert_eq!(headers.get("Spas-AccessKey").unwrap(), "ak");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
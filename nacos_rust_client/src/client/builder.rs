@@ -1,12 +1,19 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
+#[cfg(feature = "tls")]
+use super::TlsConfig;
 use super::{
-    config_client::inner_client::ConfigInnerRequestClient, nacos_client::ActixSystemActorSetCmd,
-    naming_client::InnerNamingRequestClient, AuthInfo, ClientInfo, ConfigClient, HostInfo,
-    NamingClient, ServerEndpointInfo,
+    config_client::{inner_client::ConfigInnerRequestClient, ConfigInnerActor, ConfigSnapshot},
+    nacos_client::ActorRuntime,
+    naming_client::{InnerNamingRequestClient, NamingCache, NamingProtectPolicy},
+    registry::registry,
+    AccessKeyAuthProvider, AccessTokenPosition, AddressServer, AuthInfo, AuthProvider, AuthState,
+    AuthStateListener, ClientInfo, ConfigClient, ConnectionPolicy, HostInfo, NamingClient,
+    ServerEndpointInfo,
 };
 use crate::client::auth::AuthActor;
-use crate::{conn_manage::manage::ConnManage, init_global_system_actor};
+use crate::conn_manage::manage::ConnManage;
+use crate::error::Result;
 
 #[derive(Clone, Debug)]
 pub struct ClientBuilder {
@@ -15,6 +22,10 @@ pub struct ClientBuilder {
     auth_info: Option<AuthInfo>,
     use_grpc: bool,
     client_info: ClientInfo,
+    config_snapshot_dir: Option<PathBuf>,
+    naming_cache_dir: Option<PathBuf>,
+    naming_protect_policy: NamingProtectPolicy,
+    registry_name: Option<String>,
 }
 
 impl Default for ClientBuilder {
@@ -32,6 +43,10 @@ impl ClientBuilder {
             auth_info: None,
             use_grpc: true,
             client_info: Default::default(),
+            config_snapshot_dir: None,
+            naming_cache_dir: None,
+            naming_protect_policy: Default::default(),
+            registry_name: None,
         }
     }
 
@@ -41,7 +56,9 @@ impl ClientBuilder {
     }
 
     pub fn set_endpoint_addrs(mut self, addrs: &str) -> Self {
-        self.endpoint = ServerEndpointInfo::new(addrs);
+        self.endpoint = self
+            .endpoint
+            .with_hosts(ServerEndpointInfo::new(addrs).hosts);
         self
     }
 
@@ -51,7 +68,68 @@ impl ClientBuilder {
     }
 
     pub fn set_hosts(mut self, hosts: Vec<HostInfo>) -> Self {
-        self.endpoint = ServerEndpointInfo { hosts };
+        self.endpoint = self.endpoint.with_hosts(hosts);
+        self
+    }
+
+    /// 从地址服务器(endpoint 模式)获取服务地址,地址列表变化时自动增减连接。
+    /// 会清空之前设置的地址;之后再设置的地址只在首次拉取成功前使用
+    pub fn set_address_server(mut self, address_server: AddressServer) -> Self {
+        self.endpoint.hosts.clear();
+        self.endpoint.address_server = Some(address_server);
+        self
+    }
+
+    /// http 接口的路径前缀,默认为 `/nacos`;nacos 部署在网关的子路径下时使用。
+    /// 单个地址可通过 `HostInfo::context_path` 或 `ip:port/path` 形式的地址单独设置
+    pub fn set_context_path(mut self, context_path: String) -> Self {
+        self.endpoint.context_path = Some(context_path);
+        self
+    }
+
+    /// 使用 https 与 gRPC TLS 连接服务端,需要开启 `tls` feature;证书或私钥无法加载时返回错误
+    #[cfg(feature = "tls")]
+    pub fn set_tls(mut self, tls: TlsConfig) -> Result<Self> {
+        tls.validate()?;
+        self.endpoint.tls = Some(Arc::new(tls));
+        Ok(self)
+    }
+
+    /// 熔断、重连退避、请求重试与超时策略
+    pub fn set_connection_policy(mut self, policy: ConnectionPolicy) -> Self {
+        self.endpoint.policy = Arc::new(policy);
+        self
+    }
+
+    /// 鉴权方式,如 `AccessKeyAuthProvider`;每个请求发送前附加其返回的头部
+    pub fn set_auth_provider(mut self, provider: Arc<dyn AuthProvider>) -> Self {
+        self.endpoint.auth_provider = Some(provider);
+        self
+    }
+
+    /// http 请求携带 accessToken 的位置;设置为请求头可以避免 token 出现在访问日志中
+    pub fn set_access_token_position(mut self, position: AccessTokenPosition) -> Self {
+        self.endpoint.access_token_position = position;
+        self
+    }
+
+    /// 鉴权状态变化时回调,如登录失败、token 过期
+    pub fn set_auth_state_listener(
+        mut self,
+        listener: impl Fn(AuthState) + Send + Sync + 'static,
+    ) -> Self {
+        self.endpoint.auth_state_listener = Some(AuthStateListener::new(listener));
+        self
+    }
+
+    /// 从环境变量读取鉴权信息:`NACOS_USERNAME`、`NACOS_PASSWORD` 与 `NACOS_ACCESS_KEY`、`NACOS_SECRET_KEY`
+    pub fn set_auth_from_env(mut self) -> Self {
+        if let Some(auth_info) = AuthInfo::from_env() {
+            self.auth_info = Some(auth_info);
+        }
+        if let Some(provider) = AccessKeyAuthProvider::from_env() {
+            self.endpoint.auth_provider = Some(Arc::new(provider));
+        }
         self
     }
 
@@ -85,6 +163,45 @@ impl ClientBuilder {
         self
     }
 
+    /// 配置本地快照目录;服务端都不可用时 get_config 会回退到快照内容
+    pub fn set_config_snapshot_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.config_snapshot_dir = dir;
+        self
+    }
+
+    /// 配置服务实例本地缓存目录;服务端都不可用时查询实例会回退到缓存内容
+    pub fn set_naming_cache_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.naming_cache_dir = dir;
+        self
+    }
+
+    /// 配置实例推送保护策略,如忽略空推送
+    pub fn set_naming_protect_policy(mut self, policy: NamingProtectPolicy) -> Self {
+        self.naming_protect_policy = policy;
+        self
+    }
+
+    /// 创建后按名称注册到全局注册表,可通过 `registry().get(name)` 获取;
+    /// 同时使用多个集群或命名空间时用名称区分
+    pub fn set_registry_name(mut self, name: Option<String>) -> Self {
+        self.registry_name = name;
+        self
+    }
+
+    fn register_clients(
+        registry_name: Option<String>,
+        config_client: &Arc<ConfigClient>,
+        naming_client: &Arc<NamingClient>,
+    ) {
+        if let Some(name) = registry_name {
+            registry().register(
+                &name,
+                Some(config_client.clone()),
+                Some(naming_client.clone()),
+            );
+        }
+    }
+
     pub fn build_config_client(self) -> Arc<ConfigClient> {
         let (config_client, _) = self.build();
         config_client
@@ -96,31 +213,64 @@ impl ClientBuilder {
     }
 
     pub fn build(self) -> (Arc<ConfigClient>, Arc<NamingClient>) {
+        self.build_at(ActorRuntime::global())
+    }
+
+    pub async fn build_config_client_async(self) -> Result<Arc<ConfigClient>> {
+        let (config_client, _) = self.build_async().await?;
+        Ok(config_client)
+    }
+
+    pub async fn build_naming_client_async(self) -> Result<Arc<NamingClient>> {
+        let (_, naming_client) = self.build_async().await?;
+        Ok(naming_client)
+    }
+
+    /// 异步创建客户端,不会阻塞调用线程。
+    /// 在 actix system 中调用时(如 actix-web、`#[actix_rt::test]`),后台 actor 直接运行在调用方的运行时上,
+    /// 此时不会设置为最后创建的客户端,`get_last_config_client` 等获取不到该客户端,可通过 `set_registry_name` 按名称注册;
+    /// 不支持直接运行在调用方的 tokio 运行时上:actor 需要 actix system,在其它运行时(如 `#[tokio::test]`)中调用时,
+    /// 后台 actor 运行在单独线程的全局 actix system 中
+    pub async fn build_async(self) -> Result<(Arc<ConfigClient>, Arc<NamingClient>)> {
+        Ok(self.build_at(ActorRuntime::current_or_global()))
+    }
+
+    fn build_at(self, runtime: ActorRuntime) -> (Arc<ConfigClient>, Arc<NamingClient>) {
         let use_grpc = self.use_grpc;
         let auth_info = self.auth_info;
         let endpoint = Arc::new(self.endpoint);
         let namespace_id = self.tenant.clone();
         let tenant = self.tenant;
         let current_ip = self.client_info.client_ip.clone();
-        let auth_actor = AuthActor::init_auth_actor(endpoint.clone(), auth_info.clone());
+        let config_snapshot = self
+            .config_snapshot_dir
+            .map(|dir| Arc::new(ConfigSnapshot::new(dir)));
+        let naming_cache = self
+            .naming_cache_dir
+            .map(|dir| Arc::new(NamingCache::new(dir)));
+        let auth_actor = runtime.start(AuthActor::new(endpoint.clone(), auth_info.clone()));
 
         let conn_manage = ConnManage::new(
             endpoint.hosts.clone(),
             use_grpc,
             auth_info.clone(),
-            Default::default(),
+            endpoint.policy.breaker.clone(),
             Arc::new(self.client_info),
             auth_actor.clone(),
-        );
-        let conn_manage_addr = conn_manage.start_at_global_system();
+        )
+        .set_endpoint(&endpoint);
+        let conn_manage_addr = runtime.start(conn_manage);
         let request_client =
             InnerNamingRequestClient::new_with_endpoint(endpoint.clone(), Some(auth_actor.clone()));
-        let addrs = NamingClient::init_register(
+        let addrs = NamingClient::init_register_at(
+            &runtime,
             namespace_id.clone(),
             current_ip.clone(),
             request_client,
             Some(conn_manage_addr.clone().downgrade()),
             use_grpc,
+            naming_cache,
+            self.naming_protect_policy,
         );
         let naming_client = Arc::new(NamingClient {
             namespace_id,
@@ -129,28 +279,31 @@ impl ClientBuilder {
             current_ip,
             _conn_manage_addr: conn_manage_addr.clone(),
         });
-        let system_addr = init_global_system_actor();
-        system_addr.do_send(ActixSystemActorSetCmd::LastNamingClient(
-            naming_client.clone(),
-        ));
 
         let request_client =
             ConfigInnerRequestClient::new_with_endpoint(endpoint, Some(auth_actor.clone()));
-        let config_inner_addr = ConfigClient::init_register(
+        let change_sender = ConfigClient::new_change_sender();
+        let actor = ConfigInnerActor::new(
             request_client.clone(),
-            Some(conn_manage_addr.clone().downgrade()),
             use_grpc,
+            Some(conn_manage_addr.clone().downgrade()),
+            config_snapshot.clone(),
+            change_sender.clone(),
         );
+        let config_inner_addr = runtime.start(actor);
         let config_client = Arc::new(ConfigClient {
             tenant,
             request_client,
             config_inner_addr,
             conn_manage_addr,
+            snapshot: config_snapshot,
+            change_sender,
         });
-        //let system_addr = init_global_system_actor();
-        system_addr.do_send(ActixSystemActorSetCmd::LastConfigClient(
-            config_client.clone(),
-        ));
+        if runtime.is_global() {
+            registry().set_last_naming_client(naming_client.clone());
+            registry().set_last_config_client(config_client.clone());
+        }
+        Self::register_clients(self.registry_name, &config_client, &naming_client);
         (config_client, naming_client)
     }
 }
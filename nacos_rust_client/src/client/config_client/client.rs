@@ -3,14 +3,22 @@ use std::sync::Arc;
 
 use actix::{Addr, WeakAddr};
 
+use serde::de::DeserializeOwned;
+use tokio::sync::{broadcast, watch};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
 use super::{
     config_key::ConfigKey,
+    format::ConfigFormat,
     inner::{ConfigInnerActor, ConfigInnerCmd},
     inner_client::ConfigInnerRequestClient,
-    listener::ConfigListener,
+    listener::{ConfigListener, WatchListener},
+    model::{ConfigChangeEvent, ConfigPublishResult, ConfigValue},
+    snapshot::ConfigSnapshot,
 };
 use crate::client::api_model::{ConsoleResult, NamespaceInfo};
 use crate::client::config_client::api_model::{ConfigInfoDto, ConfigQueryParams, ConfigSearchPage};
+use crate::error::{Error, Result};
 use crate::{
     client::{
         auth::AuthActor,
@@ -20,7 +28,7 @@ use crate::{
     },
     conn_manage::{
         conn_msg::{ConfigRequest, ConfigResponse},
-        manage::ConnManage,
+        manage::{ConnManage, ConnManageCmd},
     },
     init_global_system_actor,
 };
@@ -30,8 +38,13 @@ pub struct ConfigClient {
     pub(crate) request_client: ConfigInnerRequestClient,
     pub(crate) config_inner_addr: Addr<ConfigInnerActor>,
     pub(crate) conn_manage_addr: Addr<ConnManage>,
+    pub(crate) snapshot: Option<Arc<ConfigSnapshot>>,
+    pub(crate) change_sender: broadcast::Sender<ConfigChangeEvent>,
 }
 
+//changes 接收方处理过慢时丢弃的事件数上限
+const CONFIG_CHANGE_CAPACITY: usize = 128;
+
 impl Drop for ConfigClient {
     fn drop(&mut self) {
         self.config_inner_addr.do_send(ConfigInnerCmd::Close);
@@ -44,6 +57,7 @@ impl ConfigClient {
         let use_grpc = false;
         let endpoint = Arc::new(ServerEndpointInfo {
             hosts: vec![host.clone()],
+            ..Default::default()
         });
         let auth_actor = AuthActor::init_auth_actor(endpoint.clone(), None);
         let conn_manage = ConnManage::new(
@@ -57,10 +71,13 @@ impl ConfigClient {
         let conn_manage_addr = conn_manage.start_at_global_system();
         let request_client =
             ConfigInnerRequestClient::new_with_endpoint(endpoint, Some(auth_actor.clone()));
+        let change_sender = Self::new_change_sender();
         let config_inner_addr = Self::init_register(
             request_client.clone(),
             Some(conn_manage_addr.clone().downgrade()),
             use_grpc,
+            None,
+            change_sender.clone(),
         );
         //request_client.set_auth_addr(auth_addr);
         let r = Arc::new(Self {
@@ -68,6 +85,8 @@ impl ConfigClient {
             request_client,
             config_inner_addr,
             conn_manage_addr,
+            snapshot: None,
+            change_sender,
         });
         let system_addr = init_global_system_actor();
         system_addr.do_send(ActixSystemActorSetCmd::LastConfigClient(r.clone()));
@@ -89,29 +108,46 @@ impl ConfigClient {
         let conn_manage_addr = conn_manage.start_at_global_system();
         let request_client =
             ConfigInnerRequestClient::new_with_endpoint(endpoint, Some(auth_actor));
+        let change_sender = Self::new_change_sender();
         let config_inner_addr = Self::init_register(
             request_client.clone(),
             Some(conn_manage_addr.clone().downgrade()),
             use_grpc,
+            None,
+            change_sender.clone(),
         );
         let r = Arc::new(Self {
             tenant,
             request_client,
             config_inner_addr,
             conn_manage_addr,
+            snapshot: None,
+            change_sender,
         });
         let system_addr = init_global_system_actor();
         system_addr.do_send(ActixSystemActorSetCmd::LastConfigClient(r.clone()));
         r
     }
 
+    pub(crate) fn new_change_sender() -> broadcast::Sender<ConfigChangeEvent> {
+        broadcast::channel(CONFIG_CHANGE_CAPACITY).0
+    }
+
     pub(crate) fn init_register(
         request_client: ConfigInnerRequestClient,
         conn_manage_addr: Option<WeakAddr<ConnManage>>,
         use_grpc: bool,
+        snapshot: Option<Arc<ConfigSnapshot>>,
+        change_sender: broadcast::Sender<ConfigChangeEvent>,
     ) -> Addr<ConfigInnerActor> {
         let system_addr = init_global_system_actor();
-        let actor = ConfigInnerActor::new(request_client, use_grpc, conn_manage_addr);
+        let actor = ConfigInnerActor::new(
+            request_client,
+            use_grpc,
+            conn_manage_addr,
+            snapshot,
+            change_sender,
+        );
         let (tx, rx) = std::sync::mpsc::sync_channel(1);
         let msg = ActixSystemCmd::ConfigInnerActor(actor, tx);
         system_addr.do_send(msg);
@@ -129,51 +165,137 @@ impl ConfigClient {
         }
     }
 
-    pub async fn get_namespace_list(&self) -> anyhow::Result<ConsoleResult<Vec<NamespaceInfo>>> {
-        self.request_client.get_namespace_list().await
+    pub async fn get_namespace_list(&self) -> Result<ConsoleResult<Vec<NamespaceInfo>>> {
+        Ok(self.request_client.get_namespace_list().await?)
     }
 
     pub async fn query_blur_config_page(
         &self,
         mut params: ConfigQueryParams,
-    ) -> anyhow::Result<ConfigSearchPage<ConfigInfoDto>> {
+    ) -> Result<ConfigSearchPage<ConfigInfoDto>> {
         if params.tenant.is_none() {
             params.tenant = Some(self.tenant.to_owned());
         }
-        self.request_client.query_blur_config_page(params).await
+        Ok(self.request_client.query_blur_config_page(params).await?)
     }
 
     pub async fn query_accurate_config_page(
         &self,
         mut params: ConfigQueryParams,
-    ) -> anyhow::Result<ConfigSearchPage<ConfigInfoDto>> {
+    ) -> Result<ConfigSearchPage<ConfigInfoDto>> {
         if params.tenant.is_none() {
             params.tenant = Some(self.tenant.to_owned());
         }
-        self.request_client.query_accurate_config_page(params).await
+        Ok(self
+            .request_client
+            .query_accurate_config_page(params)
+            .await?)
+    }
+
+    pub async fn get_config(&self, key: &ConfigKey) -> Result<String> {
+        Ok(self.get_config_value(key).await?.content)
     }
 
-    pub async fn get_config(&self, key: &ConfigKey) -> anyhow::Result<String> {
+    /// 获取配置;服务端不可用且配置了本地快照时,返回快照内容并标记 from_snapshot。
+    /// 服务端已响应的错误(配置不存在、鉴权失败、服务端错误等)直接返回,不会使用快照
+    pub async fn get_config_value(&self, key: &ConfigKey) -> Result<ConfigValue> {
+        match self.request_config(key).await {
+            Ok((content, md5)) => {
+                if let Some(snapshot) = &self.snapshot {
+                    if let Err(err) = snapshot.write(key, &content) {
+                        log::warn!("write config snapshot error,{:?},{}", key, err);
+                    }
+                }
+                Ok(ConfigValue {
+                    content,
+                    md5,
+                    from_snapshot: false,
+                })
+            }
+            Err(err) => {
+                let err = Error::from(err);
+                if err.is_server_reply() {
+                    return Err(err);
+                }
+                if let Some(content) = self.snapshot.as_ref().and_then(|e| e.read(key)) {
+                    log::warn!("get config error,use local snapshot,{:?},{}", key, err);
+                    return Ok(ConfigValue {
+                        md5: get_md5(&content),
+                        content,
+                        from_snapshot: true,
+                    });
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn request_config(&self, key: &ConfigKey) -> anyhow::Result<(String, String)> {
         let cmd = ConfigRequest::GetConfig(key.clone());
         let res: ConfigResponse = self.conn_manage_addr.send(cmd).await??;
         match res {
-            ConfigResponse::ConfigValue(content, _md5) => Ok(content),
-            _ => Err(anyhow::anyhow!("get config error")),
+            ConfigResponse::ConfigValue(content, md5) => Ok((content, md5)),
+            _ => Err(Error::Decode("get config error,unexpected response".to_owned()).into()),
         }
     }
 
-    pub async fn set_config(&self, key: &ConfigKey, value: &str) -> anyhow::Result<()> {
+    /// 获取配置并按 data_id 后缀推断的格式解析,无法推断时按 json 解析
+    pub async fn get_typed<T: DeserializeOwned>(&self, key: &ConfigKey) -> Result<T> {
+        let format = ConfigFormat::from_data_id(&key.data_id).unwrap_or(ConfigFormat::Json);
+        self.get_typed_with_format(key, format).await
+    }
+
+    pub async fn get_typed_with_format<T: DeserializeOwned>(
+        &self,
+        key: &ConfigKey,
+        format: ConfigFormat,
+    ) -> Result<T> {
+        let content = self.get_config(key).await?;
+        format.decode(&content)
+    }
+
+    pub async fn set_config(&self, key: &ConfigKey, value: &str) -> Result<()> {
         let cmd = ConfigRequest::SetConfig(key.clone(), value.to_owned());
         let _res: ConfigResponse = self.conn_manage_addr.send(cmd).await??;
         Ok(())
     }
 
-    pub async fn del_config(&self, key: &ConfigKey) -> anyhow::Result<()> {
+    /// 仅当服务端当前内容的 md5 与 expected_md5 一致时才发布,否则返回 `ConfigPublishResult::Conflict`
+    pub async fn set_config_cas(
+        &self,
+        key: &ConfigKey,
+        value: &str,
+        expected_md5: &str,
+    ) -> Result<ConfigPublishResult> {
+        let cmd =
+            ConfigRequest::CasSetConfig(key.clone(), value.to_owned(), expected_md5.to_owned());
+        match self.conn_manage_addr.send(cmd).await?? {
+            ConfigResponse::CasConflict => Ok(ConfigPublishResult::Conflict),
+            _ => Ok(ConfigPublishResult::Published),
+        }
+    }
+
+    pub async fn del_config(&self, key: &ConfigKey) -> Result<()> {
         let cmd = ConfigRequest::DeleteConfig(key.clone());
         let _res: ConfigResponse = self.conn_manage_addr.send(cmd).await??;
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.remove(key);
+        }
         Ok(())
     }
 
+    /// 取消全部监听并关闭连接;完成后客户端不可再使用
+    pub async fn shutdown(&self) -> Result<()> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.config_inner_addr.do_send(ConfigInnerCmd::Shutdown(tx));
+        let result = rx.await.unwrap_or(Ok(()));
+        self.conn_manage_addr
+            .send(ConnManageCmd::DetachConfigClient)
+            .await
+            .ok();
+        Ok(result?)
+    }
+
     /*
     pub(crate) async fn listene(&self,content:&str,timeout:Option<u64>) -> anyhow::Result<Vec<ConfigKey>> {
         self.request_client.listene(content, timeout).await
@@ -183,7 +305,7 @@ impl ConfigClient {
     pub async fn subscribe<T: ConfigListener + Send + 'static>(
         &self,
         listener: Box<T>,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let key = listener.get_key();
         self.subscribe_with_key(key, listener).await
     }
@@ -192,23 +314,49 @@ impl ConfigClient {
         &self,
         key: ConfigKey,
         listener: Box<T>,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let id = 0u64;
-        let md5 = match self.get_config(&key).await {
-            Ok(text) => {
-                listener.change(&key, &text);
-                get_md5(&text)
+        let md5 = match self.get_config_value(&key).await {
+            Ok(value) => {
+                listener.change(&key, &value.content);
+                value.md5
+            }
+            Err(err) => {
+                //配置不存在或暂时不可用时,等待后续变更通知
+                if !err.is_not_found() {
+                    log::warn!("subscribe get config error,{:?},{}", &key, err);
+                }
+                "".to_owned()
             }
-            Err(_) => "".to_owned(),
         };
         let msg = ConfigInnerCmd::SUBSCRIBE(key, id, md5, listener);
-        self.config_inner_addr.do_send(msg);
+        self.config_inner_addr.send(msg).await??;
         //let msg=ConfigInnerMsg::SUBSCRIBE(key,id,md5,listener);
         //self.subscribe_sender.send(msg).await;
         Ok(())
     }
 
-    pub async fn unsubscribe(&self, key: ConfigKey) -> anyhow::Result<()> {
+    /// 订阅配置并返回 watch 通道,可在异步代码中 `changed().await` 等待变更;
+    /// 配置不存在或被删除时值为 None,所有接收方关闭后自动取消订阅
+    pub async fn watch(&self, key: &ConfigKey) -> Result<watch::Receiver<Option<Arc<String>>>> {
+        let (tx, rx) = watch::channel(None);
+        let listener = Box::new(WatchListener::new(key.clone(), tx));
+        self.subscribe_with_key(key.clone(), listener).await?;
+        Ok(rx)
+    }
+
+    /// 已订阅(subscribe/watch)配置的变更流,配置被删除时事件的 content 为 None;处理过慢丢失的事件会被跳过
+    pub fn changes(&self) -> impl Stream<Item = ConfigChangeEvent> {
+        BroadcastStream::new(self.change_sender.subscribe()).filter_map(|e| match e {
+            Ok(event) => Some(event),
+            Err(err) => {
+                log::warn!("config changes stream,{}", err);
+                None
+            }
+        })
+    }
+
+    pub async fn unsubscribe(&self, key: ConfigKey) -> Result<()> {
         let id = 0u64;
         let msg = ConfigInnerCmd::REMOVE(key, id);
         self.config_inner_addr.do_send(msg);
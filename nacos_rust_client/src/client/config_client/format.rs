@@ -0,0 +1,234 @@
+use serde::de::{
+    self, value::MapDeserializer, DeserializeOwned, Deserializer, IntoDeserializer, Unexpected,
+    Visitor,
+};
+use serde::forward_to_deserialize_any;
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+
+/// 配置内容格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+    Properties,
+}
+
+impl ConfigFormat {
+    /// 按 data_id 的后缀推断格式,如 `app.yaml`
+    pub fn from_data_id(data_id: &str) -> Option<Self> {
+        let (_, ext) = data_id.rsplit_once('.')?;
+        match ext.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            "properties" => Some(Self::Properties),
+            _ => None,
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, content: &str) -> Result<T> {
+        let res = match self {
+            Self::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            Self::Properties => {
+                let map = Self::parse_properties(content)
+                    .into_iter()
+                    .map(|(k, v)| (k, PropertyValue(v)));
+                T::deserialize(MapDeserializer::new(map))
+                    .map_err(|e: de::value::Error| e.to_string())
+            }
+        };
+        res.map_err(Error::Decode)
+    }
+
+    /// 解析 properties 文本,值统一为字符串;按类型解析见 `decode`
+    pub fn parse_properties(content: &str) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        let mut line_buf = String::new();
+        for line in content.lines() {
+            let line = line.trim_start();
+            if line_buf.is_empty() && (line.is_empty() || line.starts_with(['#', '!'])) {
+                continue;
+            }
+            if let Some(part) = line.strip_suffix('\\') {
+                line_buf.push_str(part);
+                continue;
+            }
+            line_buf.push_str(line);
+            let entry = std::mem::take(&mut line_buf);
+            let (key, value) = match entry.find(['=', ':']) {
+                Some(pos) => (&entry[..pos], &entry[pos + 1..]),
+                None => (entry.as_str(), ""),
+            };
+            map.insert(key.trim().to_owned(), value.trim().to_owned());
+        }
+        map
+    }
+}
+
+/// properties 的值,按目标字段的类型解析,如 `port=8080` 可解析为 u16
+struct PropertyValue(String);
+
+impl<'de> IntoDeserializer<'de, de::value::Error> for PropertyValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parse {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+                match self.0.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::invalid_value(Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for PropertyValue {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    deserialize_parse! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Foo {
+        name: String,
+        number: u64,
+    }
+
+    #[test]
+    fn test_from_data_id() {
+        assert_eq!(
+            ConfigFormat::from_data_id("app.yml"),
+            Some(ConfigFormat::Yaml)
+        );
+        assert_eq!(
+            ConfigFormat::from_data_id("app.TOML"),
+            Some(ConfigFormat::Toml)
+        );
+        assert_eq!(ConfigFormat::from_data_id("app"), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let foo = Foo {
+            name: "foo".to_owned(),
+            number: 1,
+        };
+        let v: Foo = ConfigFormat::Json
+            .decode(r#"{"name":"foo","number":1}"#)
+            .unwrap();
+        assert_eq!(v, foo);
+        let v: Foo = ConfigFormat::Yaml.decode("name: foo\nnumber: 1\n").unwrap();
+        assert_eq!(v, foo);
+        let v: Foo = ConfigFormat::Toml
+            .decode("name = \"foo\"\nnumber = 1\n")
+            .unwrap();
+        assert_eq!(v, foo);
+        assert!(matches!(
+            ConfigFormat::Json.decode::<Foo>("{bad"),
+            Err(Error::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_properties() {
+        let content = "# comment\na=1\nb : 2\nc=x\\\n  y\n\n!skip\nd\n";
+        let map = ConfigFormat::parse_properties(content);
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get("a").unwrap(), "1");
+        assert_eq!(map.get("b").unwrap(), "2");
+        assert_eq!(map.get("c").unwrap(), "xy");
+        assert_eq!(map.get("d").unwrap(), "");
+        let v: HashMap<String, String> = ConfigFormat::Properties.decode(content).unwrap();
+        assert_eq!(v, map);
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Server {
+        host: String,
+        port: u16,
+        enabled: bool,
+        ratio: Option<f64>,
+        timeout: Option<u64>,
+    }
+
+    #[test]
+    fn test_decode_typed_properties() {
+        let content = "host=127.0.0.1\nport=8080\nenabled=true\nratio=0.5\ntimeout=\n";
+        let v: Server = ConfigFormat::Properties.decode(content).unwrap();
+        assert_eq!(
+            v,
+            Server {
+                host: "127.0.0.1".to_owned(),
+                port: 8080,
+                enabled: true,
+                ratio: Some(0.5),
+                timeout: None,
+            }
+        );
+        let err = ConfigFormat::Properties
+            .decode::<Server>("host=a\nport=x\nenabled=true\n")
+            .unwrap_err();
+        assert!(matches!(err, Error::Decode(_)));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use actix::{prelude::*, WeakAddr};
 
@@ -8,13 +8,15 @@ use crate::{
         conn_msg::{ConfigRequest, ConfigResponse},
         manage::{ConnManage, ConnManageCmd},
     },
+    error::Error,
 };
 
 use super::{
     config_key::ConfigKey,
     inner_client::ConfigInnerRequestClient,
     listener::{ConfigListener, ListenerValue},
-    model::NotifyConfigItem,
+    model::{ConfigChangeEvent, NotifyConfigItem},
+    snapshot::ConfigSnapshot,
 };
 
 pub struct ConfigInnerActor {
@@ -22,6 +24,8 @@ pub struct ConfigInnerActor {
     subscribe_map: HashMap<ConfigKey, ListenerValue>,
     conn_manage: Option<WeakAddr<ConnManage>>,
     use_grpc: bool,
+    snapshot: Option<Arc<ConfigSnapshot>>,
+    change_sender: tokio::sync::broadcast::Sender<ConfigChangeEvent>,
 }
 
 //type ConfigInnerHandleResultSender = tokio::sync::oneshot::Sender<ConfigInnerHandleResult>;
@@ -37,7 +41,11 @@ pub enum ConfigInnerCmd {
     ),
     REMOVE(ConfigKey, u64),
     Notify(Vec<NotifyConfigItem>),
+    /// 配置已被删除
+    NotifyDelete(ConfigKey),
     Close,
+    /// 取消全部监听后停止
+    Shutdown(tokio::sync::oneshot::Sender<anyhow::Result<()>>),
     GrpcResubscribe,
 }
 
@@ -51,20 +59,72 @@ impl ConfigInnerActor {
         request_client: ConfigInnerRequestClient,
         use_grpc: bool,
         conn_manage: Option<WeakAddr<ConnManage>>,
+        snapshot: Option<Arc<ConfigSnapshot>>,
+        change_sender: tokio::sync::broadcast::Sender<ConfigChangeEvent>,
     ) -> Self {
         Self {
             request_client,
             subscribe_map: Default::default(),
             conn_manage,
             use_grpc,
+            snapshot,
+            change_sender,
         }
     }
 
     fn do_change_config(&mut self, key: &ConfigKey, content: String) {
+        if let Some(snapshot) = &self.snapshot {
+            if let Err(err) = snapshot.write(key, &content) {
+                log::warn!("write config snapshot error,{:?},{}", key, err);
+            }
+        }
         let md5 = get_md5(&content);
         if let Some(v) = self.subscribe_map.get_mut(key) {
-            v.md5 = md5;
+            v.md5 = md5.clone();
             v.notify(key, &content);
+            //没有 changes 的接收方时忽略
+            self.change_sender
+                .send(ConfigChangeEvent {
+                    key: key.clone(),
+                    content: Some(Arc::new(content)),
+                    md5,
+                })
+                .ok();
+            if v.remove_closed() == 0 {
+                self.remove_subscribe(key);
+            }
+        }
+    }
+
+    fn do_delete_config(&mut self, key: &ConfigKey) {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.remove(key);
+        }
+        if let Some(v) = self.subscribe_map.get_mut(key) {
+            v.md5 = "".to_owned();
+            v.notify_delete(key);
+            self.change_sender
+                .send(ConfigChangeEvent {
+                    key: key.clone(),
+                    content: None,
+                    md5: "".to_owned(),
+                })
+                .ok();
+            if v.remove_closed() == 0 {
+                self.remove_subscribe(key);
+            }
+        }
+    }
+
+    /// 取消监听该配置
+    fn remove_subscribe(&mut self, key: &ConfigKey) {
+        if self.subscribe_map.remove(key).is_some() && self.use_grpc {
+            if let Some(Some(addr)) = self.conn_manage.as_ref().map(WeakAddr::upgrade) {
+                addr.do_send(ConfigRequest::Listen(
+                    vec![(key.clone(), "".to_owned())],
+                    false,
+                ));
+            }
         }
     }
 
@@ -108,10 +168,15 @@ impl ConfigInnerActor {
                             Self::send(&addr, ConfigRequest::V1Listen(content.clone())).await
                         {
                             for key in config_keys {
-                                if let Ok(ConfigResponse::ConfigValue(value, _)) =
-                                    Self::send(&addr, ConfigRequest::GetConfig(key.clone())).await
+                                match Self::send(&addr, ConfigRequest::GetConfig(key.clone())).await
                                 {
-                                    list.push((key, value));
+                                    Ok(ConfigResponse::ConfigValue(value, _)) => {
+                                        list.push((key, Some(value)))
+                                    }
+                                    Err(err) if Error::anyhow_is_not_found(&err) => {
+                                        list.push((key, None))
+                                    }
+                                    _ => {}
                                 }
                             }
                         }
@@ -122,7 +187,10 @@ impl ConfigInnerActor {
             .into_actor(self)
             .map(|r, this, ctx| {
                 for (key, context) in r {
-                    this.do_change_config(&key, context)
+                    match context {
+                        Some(context) => this.do_change_config(&key, context),
+                        None => this.do_delete_config(&key),
+                    }
                 }
                 if !this.subscribe_map.is_empty() {
                     ctx.run_later(Duration::from_millis(5), |act, ctx| {
@@ -177,6 +245,7 @@ impl Handler<ConfigInnerCmd> for ConfigInnerActor {
                 let list = self.subscribe_map.get_mut(&key);
                 match list {
                     Some(v) => {
+                        v.remove_closed();
                         v.push(id, func);
                         if !md5.is_empty() {
                             v.md5 = md5;
@@ -206,14 +275,8 @@ impl Handler<ConfigInnerCmd> for ConfigInnerActor {
             }
             ConfigInnerCmd::REMOVE(key, id) => {
                 if let Some(v) = self.subscribe_map.get_mut(&key) {
-                    let size = v.remove(id);
-                    if size == 0 && self.subscribe_map.remove(&key).is_some() && self.use_grpc {
-                        if let Some(Some(addr)) = self.conn_manage.as_ref().map(WeakAddr::upgrade) {
-                            addr.do_send(ConfigRequest::Listen(
-                                vec![(key.clone(), "".to_owned())],
-                                false,
-                            ));
-                        }
+                    if v.remove(id) == 0 {
+                        self.remove_subscribe(&key);
                     }
                 };
                 Ok(ConfigInnerHandleResult::None)
@@ -223,12 +286,41 @@ impl Handler<ConfigInnerCmd> for ConfigInnerActor {
                 ctx.stop();
                 Ok(ConfigInnerHandleResult::None)
             }
+            ConfigInnerCmd::Shutdown(sender) => {
+                let keys: Vec<(ConfigKey, String)> = self
+                    .subscribe_map
+                    .drain()
+                    .map(|(k, _)| (k, "".to_owned()))
+                    .collect();
+                let conn_manage = self.conn_manage.take();
+                let use_grpc = self.use_grpc;
+                async move {
+                    if use_grpc && !keys.is_empty() {
+                        if let Some(Some(addr)) = conn_manage.as_ref().map(WeakAddr::upgrade) {
+                            addr.send(ConfigRequest::Listen(keys, false)).await??;
+                        }
+                    }
+                    Ok(())
+                }
+                .into_actor(self)
+                .map(|res: anyhow::Result<()>, _, ctx| {
+                    log::info!("ConfigInnerActor shutdown");
+                    sender.send(res).ok();
+                    ctx.stop();
+                })
+                .wait(ctx);
+                Ok(ConfigInnerHandleResult::None)
+            }
             ConfigInnerCmd::Notify(items) => {
                 for item in items {
                     self.do_change_config(&item.key, item.content);
                 }
                 Ok(ConfigInnerHandleResult::None)
             }
+            ConfigInnerCmd::NotifyDelete(key) => {
+                self.do_delete_config(&key);
+                Ok(ConfigInnerHandleResult::None)
+            }
             ConfigInnerCmd::GrpcResubscribe => {
                 self.grpc_resubscribe(ctx);
                 Ok(ConfigInnerHandleResult::None)
@@ -1,31 +1,39 @@
 use std::{collections::HashMap, sync::Arc};
 
-use super::{listener::ListenerItem, ConfigKey};
+use super::{
+    listener::ListenerItem,
+    model::{is_cas_conflict_message, ConfigPublishResult},
+    ConfigKey,
+};
 use crate::client;
 use crate::client::api_model::{ConsoleResult, NamespaceInfo};
 use crate::client::config_client::api_model::{ConfigInfoDto, ConfigQueryParams, ConfigSearchPage};
 use crate::client::{
-    auth::{AuthActor, AuthCmd, AuthHandleResult},
+    auth::{get_auth_headers, get_token_result, AuthActor},
     utils::Utils,
-    HostInfo, ServerEndpointInfo,
+    AuthResource, HostInfo, HttpClients, RequestOperation, ServerEndpointInfo,
 };
+use crate::error::Error;
 use actix::Addr;
 
 #[derive(Clone)]
 pub struct ConfigInnerRequestClient {
     pub(crate) endpoints: Arc<ServerEndpointInfo>,
-    pub(crate) client: reqwest::Client,
+    pub(crate) clients: HttpClients,
     pub(crate) headers: HashMap<String, String>,
     pub(crate) auth_addr: Option<Addr<AuthActor>>,
 }
 
 impl ConfigInnerRequestClient {
     pub fn new(host: HostInfo) -> Self {
-        let client = reqwest::Client::builder().build().unwrap();
-        let endpoints = ServerEndpointInfo { hosts: vec![host] };
+        let endpoints = ServerEndpointInfo {
+            hosts: vec![host],
+            ..Default::default()
+        };
+        let clients = HttpClients::new(&endpoints);
         Self {
             endpoints: Arc::new(endpoints),
-            client,
+            clients,
             headers: client::Client::build_http_headers(),
             auth_addr: None,
         }
@@ -35,10 +43,10 @@ impl ConfigInnerRequestClient {
         endpoints: Arc<ServerEndpointInfo>,
         auth_addr: Option<Addr<AuthActor>>,
     ) -> Self {
-        let client = reqwest::Client::builder().build().unwrap();
+        let clients = HttpClients::new(&endpoints);
         Self {
             endpoints,
-            client,
+            clients,
             headers: client::Client::build_http_headers(),
             auth_addr,
         }
@@ -48,16 +56,17 @@ impl ConfigInnerRequestClient {
         self.auth_addr = Some(addr);
     }
 
+    fn http_client(&self, host: &HostInfo) -> reqwest::Client {
+        self.clients.get(&self.endpoints, host)
+    }
+
     pub async fn get_token_result(&self) -> anyhow::Result<String> {
         if let Some(auth_addr) = &self.auth_addr {
-            match auth_addr.send(AuthCmd::QueryToken).await?? {
-                AuthHandleResult::None => {}
-                AuthHandleResult::Token(v) => {
-                    if v.len() > 0 {
-                        return Ok(format!("accessToken={}", &v));
-                    }
-                }
-            };
+            //登录失败时返回 Unauthorized,不再发送未鉴权的请求
+            let token = get_token_result(auth_addr).await?;
+            if !token.is_empty() {
+                return Ok(format!("accessToken={}", &token));
+            }
         }
         Ok(String::new())
     }
@@ -66,21 +75,53 @@ impl ConfigInnerRequestClient {
         self.get_token_result().await.unwrap_or_default()
     }
 
+    /// 请求头与 url 中的 token 参数;token 按 `AccessTokenPosition` 放入请求头或 url
+    async fn build_auth_headers(
+        &self,
+        resource: AuthResource,
+    ) -> anyhow::Result<(String, HashMap<String, String>)> {
+        let mut headers = self.build_headers(resource).await;
+        //登录失败时返回 Unauthorized,不再发送未鉴权的请求
+        let token_param = match &self.auth_addr {
+            Some(auth_addr) => {
+                let token = get_token_result(auth_addr).await?;
+                self.endpoints
+                    .access_token_position
+                    .apply(&token, &mut headers)
+            }
+            None => String::new(),
+        };
+        Ok((token_param, headers))
+    }
+
+    /// 请求头,包含鉴权方式计算的头部
+    async fn build_headers(&self, resource: AuthResource) -> HashMap<String, String> {
+        let mut headers = self.headers.clone();
+        if let Some(auth_addr) = &self.auth_addr {
+            match get_auth_headers(auth_addr, resource).await {
+                Ok(auth_headers) => headers.extend(auth_headers),
+                Err(err) => log::warn!("get auth headers error,{}", err),
+            }
+        }
+        headers
+    }
+
     pub(crate) async fn get_namespace_list(
         &self,
     ) -> anyhow::Result<ConsoleResult<Vec<NamespaceInfo>>> {
-        let token_param = self.get_token().await;
+        let (token_param, headers) = self.build_auth_headers(AuthResource::None).await?;
         let host = self.endpoints.select_host();
         let url = format!(
-            "http://{}:{}/nacos/v1/console/namespaces?{}",
-            &host.ip, &host.port, token_param,
+            "{}/v1/console/namespaces?{}",
+            self.endpoints.server_url(&host),
+            token_param,
         );
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "GET",
             &url,
             vec![],
-            Some(&self.headers),
+            Some(&headers),
             Some(10000),
         )
         .await?;
@@ -92,21 +133,20 @@ impl ConfigInnerRequestClient {
         mut params: ConfigQueryParams,
     ) -> anyhow::Result<ConfigSearchPage<ConfigInfoDto>> {
         params.search = Some("blur".to_string());
-        let token_param = self.get_token().await;
+        let (token_param, headers) = self.build_auth_headers(AuthResource::None).await?;
         let host = self.endpoints.select_host();
         let url = format!(
-            "http://{}:{}/nacos/v1/cs/configs?{}&{}",
-            &host.ip,
-            &host.port,
+            "{}/v1/cs/configs?{}&{}",
+            self.endpoints.server_url(&host),
             token_param,
             &serde_urlencoded::to_string(&params)?
         );
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "GET",
             &url,
             vec![],
-            Some(&self.headers),
+            Some(&headers),
             Some(10000),
         )
         .await?;
@@ -118,21 +158,20 @@ impl ConfigInnerRequestClient {
         mut params: ConfigQueryParams,
     ) -> anyhow::Result<ConfigSearchPage<ConfigInfoDto>> {
         params.search = Some("accurate".to_string());
-        let token_param = self.get_token().await;
+        let (token_param, headers) = self.build_auth_headers(AuthResource::None).await?;
         let host = self.endpoints.select_host();
         let url = format!(
-            "http://{}:{}/nacos/v1/cs/configs?{}&{}",
-            &host.ip,
-            &host.port,
+            "{}/v1/cs/configs?{}&{}",
+            self.endpoints.server_url(&host),
             token_param,
             &serde_urlencoded::to_string(&params)?
         );
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "GET",
             &url,
             vec![],
-            Some(&self.headers),
+            Some(&headers),
             Some(10000),
         )
         .await?;
@@ -148,25 +187,30 @@ impl ConfigInnerRequestClient {
             param.insert("tenant", &key.tenant);
         }
         let host = self.endpoints.select_host();
-        let token_param = self.get_token().await;
+        let (token_param, headers) = self
+            .build_auth_headers(AuthResource::config(&key.tenant, &key.group))
+            .await?;
         let url = format!(
-            "http://{}:{}/nacos/v1/cs/configs?{}&{}",
-            host.ip,
-            host.port,
+            "{}/v1/cs/configs?{}&{}",
+            self.endpoints.server_url(&host),
             token_param,
             serde_urlencoded::to_string(&param).unwrap()
         );
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "GET",
             &url,
             vec![],
-            Some(&self.headers),
-            Some(3000),
+            Some(&headers),
+            Some(
+                self.endpoints
+                    .policy
+                    .timeout_millis(RequestOperation::GetConfig),
+            ),
         )
         .await?;
         if !resp.status_is_200() {
-            return Err(anyhow::anyhow!("get config error"));
+            return Err(Error::from_http_status(resp.status, resp.get_lossy_string_body()).into());
         }
         let text = resp.get_string_body();
         log::debug!("get_config:{}", &text);
@@ -174,6 +218,25 @@ impl ConfigInnerRequestClient {
     }
 
     pub async fn set_config(&self, key: &ConfigKey, value: &str) -> anyhow::Result<()> {
+        self.do_set_config(key, value, None).await?;
+        Ok(())
+    }
+
+    pub async fn set_config_cas(
+        &self,
+        key: &ConfigKey,
+        value: &str,
+        cas_md5: &str,
+    ) -> anyhow::Result<ConfigPublishResult> {
+        self.do_set_config(key, value, Some(cas_md5)).await
+    }
+
+    async fn do_set_config(
+        &self,
+        key: &ConfigKey,
+        value: &str,
+        cas_md5: Option<&str>,
+    ) -> anyhow::Result<ConfigPublishResult> {
         let mut param: HashMap<&str, &str> = HashMap::new();
         param.insert("group", &key.group);
         param.insert("dataId", &key.data_id);
@@ -181,28 +244,43 @@ impl ConfigInnerRequestClient {
             param.insert("tenant", &key.tenant);
         }
         param.insert("content", value);
-        let token_param = self.get_token().await;
+        let (token_param, mut headers) = self
+            .build_auth_headers(AuthResource::config(&key.tenant, &key.group))
+            .await?;
+        if let Some(cas_md5) = cas_md5 {
+            param.insert("casMd5", cas_md5);
+            headers.insert("casMd5".to_owned(), cas_md5.to_owned());
+        }
         let host = self.endpoints.select_host();
         let url = format!(
-            "http://{}:{}/nacos/v1/cs/configs?{}",
-            host.ip, host.port, token_param
+            "{}/v1/cs/configs?{}",
+            self.endpoints.server_url(&host),
+            token_param
         );
 
         let body = serde_urlencoded::to_string(&param).unwrap();
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "POST",
             &url,
             body.as_bytes().to_vec(),
-            Some(&self.headers),
-            Some(3000),
+            Some(&headers),
+            Some(
+                self.endpoints
+                    .policy
+                    .timeout_millis(RequestOperation::PublishConfig),
+            ),
         )
         .await?;
         if !resp.status_is_200() {
-            log::error!("{}", resp.get_lossy_string_body());
-            return Err(anyhow::anyhow!("set config error"));
+            let resp_body = resp.get_lossy_string_body();
+            if cas_md5.is_some() && (resp.status == 409 || is_cas_conflict_message(&resp_body)) {
+                return Ok(ConfigPublishResult::Conflict);
+            }
+            log::error!("{}", resp_body);
+            return Err(Error::from_http_status(resp.status, resp_body).into());
         }
-        Ok(())
+        Ok(ConfigPublishResult::Published)
     }
 
     pub async fn del_config(&self, key: &ConfigKey) -> anyhow::Result<()> {
@@ -212,25 +290,33 @@ impl ConfigInnerRequestClient {
         if !key.tenant.is_empty() {
             param.insert("tenant", &key.tenant);
         }
-        let token_param = self.get_token().await;
+        let (token_param, headers) = self
+            .build_auth_headers(AuthResource::config(&key.tenant, &key.group))
+            .await?;
         let host = self.endpoints.select_host();
         let url = format!(
-            "http://{}:{}/nacos/v1/cs/configs?{}",
-            host.ip, host.port, token_param
+            "{}/v1/cs/configs?{}",
+            self.endpoints.server_url(&host),
+            token_param
         );
         let body = serde_urlencoded::to_string(&param).unwrap();
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "DELETE",
             &url,
             body.as_bytes().to_vec(),
-            Some(&self.headers),
-            Some(3000),
+            Some(&headers),
+            Some(
+                self.endpoints
+                    .policy
+                    .timeout_millis(RequestOperation::RemoveConfig),
+            ),
         )
         .await?;
         if !resp.status_is_200() {
-            log::error!("{}", resp.get_lossy_string_body());
-            return Err(anyhow::anyhow!("del config error"));
+            let resp_body = resp.get_lossy_string_body();
+            log::error!("{}", resp_body);
+            return Err(Error::from_http_status(resp.status, resp_body).into());
         }
         Ok(())
     }
@@ -244,17 +330,19 @@ impl ConfigInnerRequestClient {
         let timeout = timeout.unwrap_or(30000u64);
         let timeout_str = timeout.to_string();
         param.insert("Listening-Configs", content);
-        let token_param = self.get_token().await;
+        let (token_param, mut headers) = self
+            .build_auth_headers(AuthResource::config("", ""))
+            .await?;
         let host = self.endpoints.select_host();
         let url = format!(
-            "http://{}:{}/nacos/v1/cs/configs/listener?{}",
-            host.ip, host.port, token_param
+            "{}/v1/cs/configs/listener?{}",
+            self.endpoints.server_url(&host),
+            token_param
         );
         let body = serde_urlencoded::to_string(&param).unwrap();
-        let mut headers = self.headers.clone();
         headers.insert("Long-Pulling-Timeout".to_owned(), timeout_str);
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "POST",
             &url,
             body.as_bytes().to_vec(),
@@ -270,7 +358,7 @@ impl ConfigInnerRequestClient {
                 resp.status,
                 resp.get_lossy_string_body()
             );
-            return Err(anyhow::anyhow!("listener config error"));
+            return Err(Error::from_http_status(resp.status, resp.get_lossy_string_body()).into());
         }
         let text = resp.get_string_body();
         let t = format!("v={}", &text);
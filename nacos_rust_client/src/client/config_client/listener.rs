@@ -1,6 +1,10 @@
 use std::sync::Arc;
 
+use serde::de::DeserializeOwned;
+
 use super::config_key::ConfigKey;
+use super::format::ConfigFormat;
+use crate::error::{Error, Result};
 
 pub struct ListenerItem {
     pub key: ConfigKey,
@@ -84,26 +88,56 @@ impl ListenerItem {
 pub trait ConfigListener {
     fn get_key(&self) -> ConfigKey;
     fn change(&self, key: &ConfigKey, value: &str);
+
+    /// 配置被删除时调用,默认忽略
+    fn delete(&self, _key: &ConfigKey) {}
+
+    /// 返回 true 时不再通知,并从订阅中移除
+    fn is_closed(&self) -> bool {
+        false
+    }
 }
 
 pub type ListenerConvert<T> = Arc<dyn Fn(&str) -> Option<T> + Send + Sync>;
+type ListenerDecoder<T> = Arc<dyn Fn(&str) -> Result<T> + Send + Sync>;
+/// 配置解析失败回调,参数为(key,原始内容,错误)
+pub type ListenerErrorCallback = Arc<dyn Fn(&ConfigKey, &str, &Error) + Send + Sync>;
 
 #[derive(Clone)]
 pub struct ConfigDefaultListener<T> {
     key: ConfigKey,
     pub content: Arc<std::sync::RwLock<Option<Arc<T>>>>,
-    pub convert: ListenerConvert<T>,
+    decoder: ListenerDecoder<T>,
+    error_callback: Option<ListenerErrorCallback>,
 }
 
 impl<T> ConfigDefaultListener<T> {
-    pub fn new(key: ConfigKey, convert: ListenerConvert<T>) -> Self {
+    /// convert 返回 None 时保留上一次的有效值
+    pub fn new(key: ConfigKey, convert: ListenerConvert<T>) -> Self
+    where
+        T: 'static,
+    {
+        let decoder: ListenerDecoder<T> = Arc::new(move |s| {
+            convert(s).ok_or_else(|| Error::Decode("convert config content failed".to_owned()))
+        });
+        Self::new_with_decoder(key, decoder)
+    }
+
+    fn new_with_decoder(key: ConfigKey, decoder: ListenerDecoder<T>) -> Self {
         Self {
             key,
             content: Default::default(),
-            convert,
+            decoder,
+            error_callback: None,
         }
     }
 
+    /// 设置解析失败回调;解析失败时保留上一次的有效值
+    pub fn set_error_callback(mut self, error_callback: ListenerErrorCallback) -> Self {
+        self.error_callback = Some(error_callback);
+        self
+    }
+
     pub fn get_value(&self) -> Option<Arc<T>> {
         self.content.read().unwrap().as_ref().map(|c| c.clone())
     }
@@ -114,6 +148,29 @@ impl<T> ConfigDefaultListener<T> {
     }
 }
 
+impl<T: DeserializeOwned + 'static> ConfigDefaultListener<T> {
+    pub fn new_with_format(key: ConfigKey, format: ConfigFormat) -> Self {
+        Self::new_with_decoder(key, Arc::new(move |s| format.decode(s)))
+    }
+
+    pub fn json(key: ConfigKey) -> Self {
+        Self::new_with_format(key, ConfigFormat::Json)
+    }
+
+    pub fn yaml(key: ConfigKey) -> Self {
+        Self::new_with_format(key, ConfigFormat::Yaml)
+    }
+
+    pub fn toml(key: ConfigKey) -> Self {
+        Self::new_with_format(key, ConfigFormat::Toml)
+    }
+
+    /// 值按字段类型解析;也可解析为 `HashMap<String, String>`
+    pub fn properties(key: ConfigKey) -> Self {
+        Self::new_with_format(key, ConfigFormat::Properties)
+    }
+}
+
 impl<T> ConfigListener for ConfigDefaultListener<T> {
     fn get_key(&self) -> ConfigKey {
         self.key.clone()
@@ -122,13 +179,51 @@ impl<T> ConfigListener for ConfigDefaultListener<T> {
     fn change(&self, key: &ConfigKey, value: &str) {
         log::debug!("ConfigDefaultListener change:{:?},{}", key, value);
         let content = self.content.clone();
-        let convert = self.convert.as_ref();
-        if let Some(value) = convert(value) {
-            Self::set_value(content, value);
+        match (self.decoder)(value) {
+            Ok(value) => Self::set_value(content, value),
+            Err(err) => {
+                log::warn!("ConfigDefaultListener decode error,{:?},{}", key, err);
+                if let Some(error_callback) = &self.error_callback {
+                    error_callback(key, value, &err);
+                }
+            }
         }
     }
 }
 
+/// 把配置写入 tokio watch 通道,用于 `ConfigClient::watch`
+pub(crate) struct WatchListener {
+    key: ConfigKey,
+    sender: tokio::sync::watch::Sender<Option<Arc<String>>>,
+}
+
+impl WatchListener {
+    pub(crate) fn new(
+        key: ConfigKey,
+        sender: tokio::sync::watch::Sender<Option<Arc<String>>>,
+    ) -> Self {
+        Self { key, sender }
+    }
+}
+
+impl ConfigListener for WatchListener {
+    fn get_key(&self) -> ConfigKey {
+        self.key.clone()
+    }
+
+    fn change(&self, _key: &ConfigKey, value: &str) {
+        self.sender.send_replace(Some(Arc::new(value.to_owned())));
+    }
+
+    fn delete(&self, _key: &ConfigKey) {
+        self.sender.send_replace(None);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
 pub(crate) struct ListenerValue {
     pub(crate) md5: String,
     listeners: Vec<(u64, Box<dyn ConfigListener + Send>)>,
@@ -149,6 +244,18 @@ impl ListenerValue {
         }
     }
 
+    pub(crate) fn notify_delete(&self, key: &ConfigKey) {
+        for (_, func) in self.listeners.iter() {
+            func.delete(key);
+        }
+    }
+
+    /// 移除已关闭的监听,返回剩余数量
+    pub(crate) fn remove_closed(&mut self) -> usize {
+        self.listeners.retain(|(_, func)| !func.is_closed());
+        self.listeners.len()
+    }
+
     pub(crate) fn remove(&mut self, id: u64) -> usize {
         let mut indexs = Vec::new();
         for i in 0..self.listeners.len() {
@@ -165,3 +272,60 @@ impl ListenerValue {
         self.listeners.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, serde::Deserialize, PartialEq)]
+    struct Foo {
+        name: String,
+    }
+
+    #[test]
+    fn test_keep_last_value_on_decode_error() {
+        let key = ConfigKey::new("foo.json", "DEFAULT_GROUP", "");
+        let errors = Arc::new(AtomicUsize::new(0));
+        let callback_errors = errors.clone();
+        let listener = ConfigDefaultListener::<Foo>::json(key.clone()).set_error_callback(
+            Arc::new(move |_, content, _| {
+                assert_eq!(content, "{bad json");
+                callback_errors.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+        listener.change(&key, r#"{"name":"v1"}"#);
+        assert_eq!(listener.get_value().unwrap().name, "v1");
+
+        //解析失败时保留上一次的有效值
+        listener.change(&key, "{bad json");
+        assert_eq!(listener.get_value().unwrap().name, "v1");
+        assert_eq!(errors.load(Ordering::SeqCst), 1);
+
+        listener.change(&key, r#"{"name":"v2"}"#);
+        assert_eq!(listener.get_value().unwrap().name, "v2");
+    }
+
+    #[test]
+    fn test_remove_closed_watch_listener() {
+        let key = ConfigKey::new("foo.json", "DEFAULT_GROUP", "");
+        let (tx, rx) = tokio::sync::watch::channel(None);
+        let (closed_tx, closed_rx) = tokio::sync::watch::channel(None);
+        let mut value = ListenerValue::new(
+            vec![
+                (0, Box::new(WatchListener::new(key.clone(), tx))),
+                (0, Box::new(WatchListener::new(key.clone(), closed_tx))),
+            ],
+            "".to_owned(),
+        );
+        drop(closed_rx);
+        value.notify(&key, "v1");
+        assert_eq!(rx.borrow().as_deref().unwrap(), "v1");
+        assert_eq!(value.remove_closed(), 1);
+
+        value.notify_delete(&key);
+        assert!(rx.borrow().is_none());
+        drop(rx);
+        assert_eq!(value.remove_closed(), 0);
+    }
+}
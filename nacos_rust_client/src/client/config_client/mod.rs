@@ -1,14 +1,21 @@
 pub mod api_model;
 pub mod client;
 pub mod config_key;
+pub mod format;
 pub mod inner;
 pub mod inner_client;
 pub mod inner_grpc_client;
 pub mod listener;
 #[warn(unused_imports)]
 pub mod model;
+pub mod snapshot;
 
 pub type ConfigClient = self::client::ConfigClient;
 pub type ConfigInnerActor = self::inner::ConfigInnerActor;
 pub type ConfigKey = self::config_key::ConfigKey;
 pub type ConfigDefaultListener<T> = self::listener::ConfigDefaultListener<T>;
+pub type ConfigFormat = self::format::ConfigFormat;
+pub type ConfigChangeEvent = self::model::ConfigChangeEvent;
+pub type ConfigPublishResult = self::model::ConfigPublishResult;
+pub type ConfigSnapshot = self::snapshot::ConfigSnapshot;
+pub type ConfigValue = self::model::ConfigValue;
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use super::ConfigKey;
 
 #[derive(Debug, Default, Clone)]
@@ -6,3 +8,31 @@ pub struct NotifyConfigItem {
     pub content: String,
     pub md5: String,
 }
+
+/// 已订阅配置的变更事件,见 `ConfigClient::changes`;配置被删除时 content 为 None,md5 为空
+#[derive(Debug, Clone)]
+pub struct ConfigChangeEvent {
+    pub key: ConfigKey,
+    pub content: Option<Arc<String>>,
+    pub md5: String,
+}
+
+/// 配置值;from_snapshot 为 true 时表示服务端不可用,值来自本地快照,可能已过期
+#[derive(Debug, Default, Clone)]
+pub struct ConfigValue {
+    pub content: String,
+    pub md5: String,
+    pub from_snapshot: bool,
+}
+
+/// cas 发布结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigPublishResult {
+    Published,
+    /// 服务端内容已被修改,md5 不匹配
+    Conflict,
+}
+
+pub(crate) fn is_cas_conflict_message(message: &str) -> bool {
+    message.to_ascii_lowercase().contains("cas publish fail")
+}
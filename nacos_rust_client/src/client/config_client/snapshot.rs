@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+use super::ConfigKey;
+use crate::client::utils::Utils;
+use crate::error::Result;
+
+/// 配置本地快照,服务端不可用时作为兜底
+#[derive(Debug, Clone)]
+pub struct ConfigSnapshot {
+    dir: PathBuf,
+}
+
+impl ConfigSnapshot {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn build_path(&self, key: &ConfigKey) -> PathBuf {
+        let tenant = if key.tenant.is_empty() {
+            "public"
+        } else {
+            &key.tenant
+        };
+        self.dir
+            .join(Utils::encode_path_segment(tenant))
+            .join(Utils::encode_path_segment(&key.group))
+            .join(Utils::encode_path_segment(&key.data_id))
+    }
+
+    pub fn read(&self, key: &ConfigKey) -> Option<String> {
+        std::fs::read_to_string(self.build_path(key)).ok()
+    }
+
+    pub fn write(&self, key: &ConfigKey, content: &str) -> Result<()> {
+        Ok(Utils::write_file_atomic(
+            &self.build_path(key),
+            content.as_bytes(),
+        )?)
+    }
+
+    pub fn remove(&self, key: &ConfigKey) {
+        std::fs::remove_file(self.build_path(key)).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_write_read() {
+        let dir = std::env::temp_dir().join(format!(
+            "nacos_config_snapshot_test_{}",
+            crate::client::now_millis()
+        ));
+        let snapshot = ConfigSnapshot::new(&dir);
+        let key = ConfigKey::new("app/../a.yaml", "foo", "");
+        assert_eq!(snapshot.read(&key), None);
+        snapshot.write(&key, "v1").unwrap();
+        snapshot.write(&key, "v2").unwrap();
+        assert_eq!(snapshot.read(&key).unwrap(), "v2");
+        assert!(snapshot.build_path(&key).starts_with(&dir));
+        snapshot.remove(&key);
+        assert_eq!(snapshot.read(&key), None);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_snapshot_concurrent_write() {
+        let dir = std::env::temp_dir().join(format!(
+            "nacos_config_snapshot_concurrent_{}",
+            crate::client::now_millis()
+        ));
+        let snapshot = ConfigSnapshot::new(&dir);
+        let key = ConfigKey::new("app.yaml", "foo", "");
+        let contents = (0..8).map(|i| format!("v{}", i)).collect::<Vec<_>>();
+        std::thread::scope(|scope| {
+            for content in contents.iter() {
+                let (snapshot, key) = (&snapshot, &key);
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        snapshot.write(key, content).unwrap();
+                    }
+                });
+            }
+        });
+        assert!(contents.contains(&snapshot.read(&key).unwrap()));
+        //不残留临时文件
+        let files = std::fs::read_dir(snapshot.build_path(&key).parent().unwrap())
+            .unwrap()
+            .count();
+        assert_eq!(files, 1);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -5,25 +5,41 @@ pub mod builder;
 pub mod config_client;
 pub mod nacos_client;
 pub mod naming_client;
+pub mod registry;
 
 pub mod utils;
 
+pub mod address_server;
 pub mod api_model;
 pub mod auth;
+pub mod auth_provider;
+pub mod tls;
 
+use crate::grpc::{ACCESS_TOKEN_HEADER, AUTHORIZATION_HEADER};
 use md5::{Digest, Md5};
 use serde::{Deserialize, Serialize};
 
+pub use self::address_server::AddressServer;
+pub use self::auth::{AuthState, AuthStateListener};
+pub use self::auth_provider::{
+    AccessKeyAuthProvider, AuthProvider, AuthResource, FileAuthProvider,
+};
 pub use self::builder::ClientBuilder;
 pub use self::config_client::ConfigClient;
 pub use self::nacos_client::NacosClient;
 pub use self::naming_client::NamingClient;
+pub use self::registry::{registry, ClientRegistry, NamedClients};
+pub use self::tls::TlsConfig;
+pub use crate::conn_manage::breaker::BreakerConfig;
+pub use crate::conn_manage::policy::{BackoffConfig, ConnectionPolicy, RequestOperation};
 
 #[derive(Debug, Clone, Default)]
 pub struct HostInfo {
     pub ip: String,
     pub port: u32,
     pub grpc_port: u32,
+    /// 该地址的 http 路径前缀,如 `/registry/nacos`;为空时使用 endpoint 的 context_path
+    pub context_path: Option<String>,
 }
 
 pub fn now_millis() -> u64 {
@@ -47,6 +63,7 @@ impl HostInfo {
             ip: ip.to_owned(),
             port,
             grpc_port: port + 1000,
+            context_path: None,
         }
     }
 
@@ -55,10 +72,21 @@ impl HostInfo {
             ip: ip.to_owned(),
             port,
             grpc_port,
+            context_path: None,
         }
     }
 
+    pub fn set_context_path(mut self, context_path: Option<String>) -> Self {
+        self.context_path = context_path;
+        self
+    }
+
+    /// 支持 `ip:port`、`ip:port#grpc_port` 与带路径前缀的 `ip:port/registry/nacos`
     pub fn parse(addr: &str) -> Self {
+        let (addr, context_path) = match addr.find('/') {
+            Some(pos) => (&addr[..pos], Some(addr[pos..].to_owned())),
+            None => (addr, None),
+        };
         let mut port = 8848u32;
         let mut grpc_port = port + 1000;
         let ip;
@@ -82,6 +110,7 @@ impl HostInfo {
             ip: ip.to_owned(),
             port,
             grpc_port,
+            context_path,
         }
     }
 }
@@ -99,6 +128,14 @@ impl AuthInfo {
             password: password.to_owned(),
         }
     }
+
+    /// 从环境变量 `NACOS_USERNAME`、`NACOS_PASSWORD` 读取,未设置时返回 None
+    pub fn from_env() -> Option<Self> {
+        let username = std::env::var("NACOS_USERNAME").unwrap_or_default();
+        let password = std::env::var("NACOS_PASSWORD").unwrap_or_default();
+        Some(Self::new(&username, &password)).filter(Self::is_valid)
+    }
+
     pub fn is_valid(&self) -> bool {
         !self.username.is_empty() && !self.password.is_empty()
     }
@@ -146,9 +183,67 @@ pub struct TokenInfo {
     pub token_ttl: u64,
 }
 
+const DEFAULT_CONTEXT_PATH: &str = "/nacos";
+
+/// 统一为 `/xxx` 的形式,根路径返回空字符串
+fn normalize_context_path(context_path: &str) -> String {
+    let context_path = context_path.trim().trim_matches('/');
+    if context_path.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", context_path)
+    }
+}
+
+/// http 请求携带 accessToken 的位置;gRPC 请求始终放在 payload 头部
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccessTokenPosition {
+    /// url 参数 `accessToken=...`,兼容旧版本服务端
+    #[default]
+    Query,
+    /// 请求头 `accessToken`
+    Header,
+    /// 请求头 `Authorization: Bearer ...`
+    Authorization,
+}
+
+impl AccessTokenPosition {
+    /// 放入请求头时返回空字符串,否则返回 url 中的 token 参数
+    pub(crate) fn apply(&self, token: &str, headers: &mut HashMap<String, String>) -> String {
+        if token.is_empty() {
+            return String::new();
+        }
+        match self {
+            Self::Query => format!("{}={}", ACCESS_TOKEN_HEADER, token),
+            Self::Header => {
+                headers.insert(ACCESS_TOKEN_HEADER.to_owned(), token.to_owned());
+                String::new()
+            }
+            Self::Authorization => {
+                headers.insert(AUTHORIZATION_HEADER.to_owned(), format!("Bearer {}", token));
+                String::new()
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ServerEndpointInfo {
     pub hosts: Vec<HostInfo>,
+    /// 设置后 http 请求使用 https,gRPC 使用 TLS 连接;通过 `ClientBuilder::set_tls` 设置
+    pub(crate) tls: Option<Arc<TlsConfig>>,
+    /// http 路径前缀,为空时使用 `/nacos`
+    pub context_path: Option<String>,
+    /// 设置后从地址服务器动态获取服务地址,hosts 只在首次拉取成功前使用
+    pub address_server: Option<AddressServer>,
+    /// 熔断、重试与超时策略
+    pub policy: Arc<ConnectionPolicy>,
+    /// 鉴权方式,如 AccessKey 签名;未设置时只使用用户名密码登录
+    pub auth_provider: Option<Arc<dyn AuthProvider>>,
+    /// 鉴权状态变化回调
+    pub auth_state_listener: Option<AuthStateListener>,
+    /// http 请求携带 accessToken 的位置,默认放在 url 参数中
+    pub access_token_position: AccessTokenPosition,
 }
 
 impl ServerEndpointInfo {
@@ -161,12 +256,132 @@ impl ServerEndpointInfo {
         if hosts.is_empty() {
             hosts.push(HostInfo::parse("127.0.0.1:8848"));
         }
-        Self { hosts }
+        Self {
+            hosts,
+            ..Default::default()
+        }
+    }
+
+    /// 使用相同的连接配置,只替换服务地址
+    pub(crate) fn with_hosts(&self, hosts: Vec<HostInfo>) -> Self {
+        Self {
+            hosts,
+            tls: self.tls.clone(),
+            context_path: self.context_path.clone(),
+            address_server: self.address_server.clone(),
+            policy: self.policy.clone(),
+            auth_provider: self.auth_provider.clone(),
+            auth_state_listener: self.auth_state_listener.clone(),
+            access_token_position: self.access_token_position,
+        }
+    }
+
+    /// 单个地址使用的连接配置,不再从地址服务器获取地址
+    pub(crate) fn for_host(&self, host: HostInfo) -> Self {
+        Self {
+            address_server: None,
+            ..self.with_hosts(vec![host])
+        }
+    }
+
+    /// http 请求地址前缀,如 `http://127.0.0.1:8848/nacos`
+    pub fn server_url(&self, host: &HostInfo) -> String {
+        let context_path = normalize_context_path(
+            host.context_path
+                .as_deref()
+                .or(self.context_path.as_deref())
+                .unwrap_or(DEFAULT_CONTEXT_PATH),
+        );
+        match &self.tls {
+            Some(tls) => format!(
+                "https://{}:{}{}",
+                tls.url_host(host),
+                host.port,
+                context_path
+            ),
+            None => format!("http://{}:{}{}", host.ip, host.port, context_path),
+        }
+    }
+
+    /// gRPC 连接地址
+    pub(crate) fn grpc_url(&self, host: &HostInfo) -> String {
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        format!("{}://{}:{}", scheme, host.ip, host.grpc_port)
+    }
+
+    /// 只有一个地址时,tls domain_name 解析到该地址;多个地址时使用 `HttpClients` 按地址创建
+    pub(crate) fn build_http_client(&self) -> reqwest::Client {
+        #[allow(unused_mut)]
+        let mut builder = reqwest::Client::builder();
+        //开启 tls feature 后,http 连接也需要 rustls 的加密实现
+        #[cfg(feature = "tls")]
+        TlsConfig::install_crypto_provider();
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            let host = match self.hosts.as_slice() {
+                [host] => Some(host),
+                _ => None,
+            };
+            //ClientBuilder::set_tls 时已检查证书
+            builder = tls
+                .apply_http(builder, host)
+                .expect("tls config is invalid");
+        }
+        builder.build().unwrap()
     }
 
-    pub fn select_host(&self) -> &HostInfo {
-        let index = naming_client::NamingUtils::select_by_weight_fn(&self.hosts, |_| 1);
-        self.hosts.get(index).unwrap()
+    /// 设置 tls domain_name 时,请求域名需要解析到所选的地址
+    pub(crate) fn pin_domain_to_host(&self) -> bool {
+        self.tls
+            .as_ref()
+            .map(|e| e.domain_name.is_some())
+            .unwrap_or(false)
+    }
+
+    /// 当前可用的服务地址;地址服务器拉取成功后使用拉取到的地址
+    pub fn current_hosts(&self) -> Vec<HostInfo> {
+        if let Some(address_server) = &self.address_server {
+            let hosts = address_server.hosts();
+            if !hosts.is_empty() {
+                return hosts;
+            }
+        }
+        self.hosts.clone()
+    }
+
+    pub fn select_host(&self) -> HostInfo {
+        let hosts = self.current_hosts();
+        let index = naming_client::NamingUtils::select_by_weight_fn(&hosts, |_| 1);
+        hosts.get(index).cloned().unwrap_or_default()
+    }
+}
+
+/// 按服务地址获取 http client;设置 tls domain_name 时请求地址使用域名,
+/// 每个地址使用单独的 client 把域名解析到该地址
+#[derive(Clone)]
+pub(crate) struct HttpClients {
+    client: reqwest::Client,
+    host_clients: Arc<std::sync::Mutex<HashMap<String, reqwest::Client>>>,
+}
+
+impl HttpClients {
+    pub(crate) fn new(endpoints: &ServerEndpointInfo) -> Self {
+        Self {
+            client: endpoints.build_http_client(),
+            host_clients: Default::default(),
+        }
+    }
+
+    pub(crate) fn get(&self, endpoints: &ServerEndpointInfo, host: &HostInfo) -> reqwest::Client {
+        if !endpoints.pin_domain_to_host() {
+            return self.client.clone();
+        }
+        self.host_clients
+            .lock()
+            .unwrap()
+            .entry(format!("{}:{}", &host.ip, host.port))
+            .or_insert_with(|| endpoints.for_host(host.clone()).build_http_client())
+            .clone()
     }
 }
 
@@ -186,6 +401,7 @@ impl Client {
     pub async fn login(
         client: &reqwest::Client,
         endpoints: Arc<ServerEndpointInfo>,
+        host: &HostInfo,
         auth_info: &AuthInfo,
     ) -> anyhow::Result<TokenInfo> {
         let mut param: HashMap<&str, &str> = HashMap::new();
@@ -197,8 +413,7 @@ impl Client {
             "application/x-www-form-urlencoded".to_owned(),
         );
 
-        let host = endpoints.select_host();
-        let url = format!("http://{}:{}/nacos/v1/auth/login", host.ip, host.port);
+        let url = format!("{}/v1/auth/login", endpoints.server_url(host));
         let resp = utils::Utils::request(
             client,
             "POST",
@@ -212,7 +427,11 @@ impl Client {
         )
         .await?;
         if !resp.status_is_200() {
-            return Err(anyhow::anyhow!("get config error"));
+            return Err(crate::error::Error::from_http_status(
+                resp.status,
+                resp.get_lossy_string_body(),
+            )
+            .into());
         }
         let text = resp.get_string_body();
         let token = serde_json::from_str(&text)?;
@@ -275,4 +494,81 @@ mod tests {
         assert_eq!(host.port, 8848);
         assert_eq!(host.grpc_port, 9848);
     }
+
+    #[test]
+    fn test_access_token_position() {
+        let mut headers = HashMap::new();
+        assert_eq!(
+            AccessTokenPosition::Query.apply("t1", &mut headers),
+            "accessToken=t1"
+        );
+        assert!(headers.is_empty());
+        assert_eq!(AccessTokenPosition::Header.apply("", &mut headers), "");
+        assert!(headers.is_empty());
+        assert_eq!(AccessTokenPosition::Header.apply("t1", &mut headers), "");
+        assert_eq!(headers.get("accessToken").unwrap(), "t1");
+        AccessTokenPosition::Authorization.apply("t1", &mut headers);
+        assert_eq!(headers.get("Authorization").unwrap(), "Bearer t1");
+    }
+
+    #[test]
+    fn test_server_url() {
+        let mut endpoint = ServerEndpointInfo::new("127.0.0.1:8848#9848");
+        let host = endpoint.hosts[0].clone();
+        assert_eq!(endpoint.server_url(&host), "http://127.0.0.1:8848/nacos");
+        assert_eq!(endpoint.grpc_url(&host), "http://127.0.0.1:9848");
+        endpoint.tls = Some(Arc::new(
+            TlsConfig::new().set_domain_name("nacos.example.com".to_owned()),
+        ));
+        assert_eq!(
+            endpoint.server_url(&host),
+            "https://nacos.example.com:8848/nacos"
+        );
+        assert_eq!(endpoint.grpc_url(&host), "https://127.0.0.1:9848");
+        let endpoint = endpoint.with_hosts(vec![HostInfo::parse("127.0.0.2:8848")]);
+        assert!(endpoint.tls.is_some());
+    }
+
+    #[test]
+    fn test_context_path() {
+        let mut endpoint =
+            ServerEndpointInfo::new("127.0.0.1:8848,127.0.0.2:8848#9848/registry/nacos/");
+        assert_eq!(endpoint.hosts[1].grpc_port, 9848);
+        assert_eq!(
+            endpoint.server_url(&endpoint.hosts[1]),
+            "http://127.0.0.2:8848/registry/nacos"
+        );
+        endpoint.context_path = Some("/".to_owned());
+        assert_eq!(
+            endpoint.server_url(&endpoint.hosts[0]),
+            "http://127.0.0.1:8848"
+        );
+    }
+
+    //每个地址的 client 把 tls 域名解析到该地址,主机名地址也会被解析
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_tls_domain_resolved_to_each_host() {
+        let listener_a = tokio::net::TcpListener::bind("127.0.0.2:0").await.unwrap();
+        let listener_b = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut endpoint = ServerEndpointInfo::new(&format!(
+            "127.0.0.2:{},localhost:{}",
+            listener_a.local_addr().unwrap().port(),
+            listener_b.local_addr().unwrap().port()
+        ));
+        endpoint.tls = Some(Arc::new(
+            TlsConfig::new().set_domain_name("nacos.example.invalid".to_owned()),
+        ));
+        let clients = HttpClients::new(&endpoint);
+        for (host, listener) in endpoint.hosts.iter().zip([listener_a, listener_b]) {
+            let url = endpoint.server_url(host);
+            assert!(url.starts_with("https://nacos.example.invalid:"), "{}", url);
+            let client = clients.get(&endpoint, host);
+            let request = tokio::spawn(async move { client.get(url).send().await });
+            let accepted =
+                tokio::time::timeout(std::time::Duration::from_secs(5), listener.accept()).await;
+            assert!(accepted.unwrap().is_ok());
+            request.abort();
+        }
+    }
 }
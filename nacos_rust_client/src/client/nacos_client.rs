@@ -4,13 +4,17 @@ use crate::conn_manage::manage::ConnManage;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use super::registry::registry;
 use super::utils;
 use super::{config_client::ConfigInnerActor, HostInfo};
 use crate::client::auth::AuthActor;
 use crate::client::naming_client::UdpWorker;
 use crate::client::naming_client::{InnerNamingListener, InnerNamingRegister};
+use actix::dev::channel;
 use actix::{prelude::*, Context};
 
+const MAILBOX_CAPACITY: usize = 16;
+
 #[derive(Debug, Clone)]
 pub struct NacosConfig {
     pub config_host: Option<HostInfo>,
@@ -41,18 +45,13 @@ impl NacosClient {
     }
 }
 
+/// 最后创建的客户端保存在全局注册表中,见 `registry()`
 #[derive(Default)]
-pub struct ActixSystemActor {
-    last_config_client: Option<Arc<ConfigClient>>,
-    last_naming_client: Option<Arc<NamingClient>>,
-}
+pub struct ActixSystemActor {}
 
 impl ActixSystemActor {
     pub fn new() -> Self {
-        Self {
-            last_config_client: None,
-            last_naming_client: None,
-        }
+        Self {}
     }
 }
 
@@ -123,12 +122,17 @@ impl Handler<ActixSystemCmd> for ActixSystemActor {
                     .unwrap_or_default();
             }
             ActixSystemCmd::Close => {
-                if let Some(naming_client) = &self.last_naming_client {
-                    naming_client.droping();
+                let naming_clients: Vec<Arc<NamingClient>> = registry()
+                    .clear()
+                    .into_iter()
+                    .filter_map(|e| e.naming_client)
+                    .collect();
+                if !naming_clients.is_empty() {
+                    for naming_client in &naming_clients {
+                        naming_client.droping();
+                    }
                     std::thread::sleep(utils::ms(100));
                 }
-                self.last_config_client = None;
-                self.last_naming_client = None;
                 ctx.stop();
                 System::current().stop();
             }
@@ -209,10 +213,10 @@ impl Handler<ActixSystemActorSetCmd> for ActixSystemActor {
     fn handle(&mut self, msg: ActixSystemActorSetCmd, _: &mut Context<Self>) -> Self::Result {
         match msg {
             ActixSystemActorSetCmd::LastConfigClient(config_client) => {
-                self.last_config_client = Some(config_client);
+                registry().set_last_config_client(config_client);
             }
             ActixSystemActorSetCmd::LastNamingClient(naming_client) => {
-                self.last_naming_client = Some(naming_client);
+                registry().set_last_naming_client(naming_client);
             }
         }
         Ok(())
@@ -242,24 +246,20 @@ impl Handler<ActixSystemActorQueryCmd> for ActixSystemActor {
     fn handle(&mut self, msg: ActixSystemActorQueryCmd, _: &mut Context<Self>) -> Self::Result {
         match msg {
             ActixSystemActorQueryCmd::QueryLastConfigClient => {
-                if let Some(client) = &self.last_config_client {
-                    return Ok(ActixSystemActorQueryResult::LastConfigClient(
-                        client.clone(),
-                    ));
+                if let Some(client) = registry().get_last_config_client() {
+                    return Ok(ActixSystemActorQueryResult::LastConfigClient(client));
                 }
             }
             ActixSystemActorQueryCmd::QueryLastNamingClient => {
-                if let Some(client) = &self.last_naming_client {
-                    return Ok(ActixSystemActorQueryResult::LastNamingClient(
-                        client.clone(),
-                    ));
+                if let Some(client) = registry().get_last_naming_client() {
+                    return Ok(ActixSystemActorQueryResult::LastNamingClient(client));
                 }
             }
             ActixSystemActorQueryCmd::SyncQueryLastConfigClient(sender) => {
-                if let Some(client) = &self.last_config_client {
+                if let Some(client) = registry().get_last_config_client() {
                     sender
                         .send(Box::new(ActixSystemActorQueryResult::LastConfigClient(
-                            client.clone(),
+                            client,
                         )))
                         .unwrap();
                 } else {
@@ -269,10 +269,10 @@ impl Handler<ActixSystemActorQueryCmd> for ActixSystemActor {
                 }
             }
             ActixSystemActorQueryCmd::SyncQueryLastNamingClient(sender) => {
-                if let Some(client) = &self.last_naming_client {
+                if let Some(client) = registry().get_last_naming_client() {
                     sender
                         .send(Box::new(ActixSystemActorQueryResult::LastNamingClient(
-                            client.clone(),
+                            client,
                         )))
                         .unwrap();
                 } else {
@@ -307,14 +307,15 @@ pub fn clear_global_system_actor() -> Option<Addr<ActixSystemActor>> {
     result
 }
 
+/// 获取全局 actix system,不存在时创建;创建时不等待 system 线程启动,消息会在启动后处理
 pub fn init_global_system_actor() -> Addr<ActixSystemActor> {
-    if let Some(r) = get_global_system_actor() {
-        r
-    } else {
-        let addr = init_register();
-        set_global_system_actor(addr.clone());
-        addr
+    let mut r = ACTIX_SYSTEM.lock().unwrap();
+    if let Some(addr) = &*r {
+        return addr.clone();
     }
+    let addr = init_register();
+    *r = Some(addr.clone());
+    addr
 }
 
 pub fn close_global_system_actor() {
@@ -325,35 +326,131 @@ pub fn close_global_system_actor() {
     }
 }
 
-pub fn get_last_config_client() -> Option<Arc<ConfigClient>> {
-    if let Some(actor) = get_global_system_actor() {
-        let (tx, rx) = std::sync::mpsc::sync_channel(1);
-        actor.do_send(ActixSystemActorQueryCmd::SyncQueryLastConfigClient(tx));
-        if let ActixSystemActorQueryResult::LastConfigClient(client) = *rx.recv().unwrap() {
-            return Some(client);
+/// 后台 actor 的运行位置
+#[derive(Clone)]
+pub(crate) enum ActorRuntime {
+    /// 调用方的 actix system(如 actix-web、`#[actix_rt::test]`)
+    Current,
+    /// 全局 actix system
+    Global(Addr<ActixSystemActor>),
+}
+
+impl ActorRuntime {
+    pub(crate) fn global() -> Self {
+        Self::Global(init_global_system_actor())
+    }
+
+    /// 在调用方的 actix system 中时使用当前运行时,否则使用全局 actix system
+    pub(crate) fn current_or_global() -> Self {
+        if System::try_current().is_some() {
+            Self::Current
+        } else {
+            Self::global()
         }
     }
-    None
-}
 
-pub fn get_last_naming_client() -> Option<Arc<NamingClient>> {
-    if let Some(actor) = get_global_system_actor() {
-        let (tx, rx) = std::sync::mpsc::sync_channel(1);
-        actor.do_send(ActixSystemActorQueryCmd::SyncQueryLastNamingClient(tx));
-        if let ActixSystemActorQueryResult::LastNamingClient(client) = *rx.recv().unwrap() {
-            return Some(client);
+    pub(crate) fn is_global(&self) -> bool {
+        matches!(self, Self::Global(_))
+    }
+
+    /// 启动 actor,不等待 actor 运行
+    pub(crate) fn start<A>(&self, actor: A) -> Addr<A>
+    where
+        A: Actor<Context = Context<A>> + Send,
+    {
+        match self {
+            Self::Current => actor.start(),
+            Self::Global(system_addr) => {
+                let (tx, rx) = channel::channel(MAILBOX_CAPACITY);
+                system_addr.do_send(ActixSystemSpawnCmd(Box::new(move || {
+                    Context::with_receiver(rx).run(actor);
+                })));
+                Addr::new(tx)
+            }
         }
     }
-    None
+}
+
+/// 在全局 actix system 线程中执行,用于启动 actor
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct ActixSystemSpawnCmd(Box<dyn FnOnce() + Send>);
+
+impl Handler<ActixSystemSpawnCmd> for ActixSystemActor {
+    type Result = ();
+    fn handle(&mut self, msg: ActixSystemSpawnCmd, _: &mut Context<Self>) -> Self::Result {
+        (msg.0)()
+    }
+}
+
+pub fn get_last_config_client() -> Option<Arc<ConfigClient>> {
+    registry().get_last_config_client()
+}
+
+pub fn get_last_naming_client() -> Option<Arc<NamingClient>> {
+    registry().get_last_naming_client()
 }
 
 fn init_register() -> Addr<ActixSystemActor> {
-    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let (tx, rx) = channel::channel(MAILBOX_CAPACITY);
     std::thread::spawn(move || {
         let rt = System::new();
-        let addrs = rt.block_on(async { ActixSystemActor::new().start() });
-        tx.send(addrs).unwrap();
+        rt.block_on(async {
+            Context::with_receiver(rx).run(ActixSystemActor::new());
+        });
         rt.run().unwrap();
     });
-    rx.recv().unwrap()
+    Addr::new(tx)
+}
+
+/// 等待退出信号(unix 下为 SIGTERM,其它平台为 ctrl-c)后关闭客户端,
+/// 注销临时实例、取消订阅并关闭连接。
+/// 调用后该信号不会再结束进程,返回后需要由调用方退出进程(如 `std::process::exit`)
+pub async fn shutdown_on_signal(
+    config_client: Option<Arc<ConfigClient>>,
+    naming_client: Option<Arc<NamingClient>>,
+) -> crate::error::Result<()> {
+    wait_terminate_signal().await?;
+    log::info!("receive terminate signal,shutdown nacos client");
+    let mut result = Ok(());
+    if let Some(naming_client) = naming_client {
+        if let Err(err) = naming_client.shutdown().await {
+            log::warn!("naming client shutdown error,{}", &err);
+            result = Err(err);
+        }
+    }
+    if let Some(config_client) = config_client {
+        if let Err(err) = config_client.shutdown().await {
+            log::warn!("config client shutdown error,{}", &err);
+            result = Err(err);
+        }
+    }
+    result
+}
+
+#[cfg(unix)]
+async fn wait_terminate_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    signal(SignalKind::terminate())?.recv().await;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn wait_terminate_signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_global_system_actor_once() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| std::thread::spawn(init_global_system_actor))
+            .collect();
+        let addrs: Vec<_> = handles.into_iter().map(|e| e.join().unwrap()).collect();
+        assert!(addrs.iter().all(|e| *e == addrs[0]));
+        assert!(get_global_system_actor().unwrap() == addrs[0]);
+    }
 }
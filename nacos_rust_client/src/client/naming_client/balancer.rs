@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use md5::{Digest, Md5};
+use rand::Rng;
+
+use super::{Instance, NamingUtils};
+
+/// 实例负载均衡策略,配合 `NamingClient::select_instance_with` 使用
+pub trait LoadBalancer: Send + Sync {
+    /// service_key 为 group@@service_name;hash_key 为路由键,只有一致性哈希等策略使用
+    fn select(
+        &self,
+        service_key: &str,
+        instances: &[Arc<Instance>],
+        hash_key: Option<&str>,
+    ) -> Option<Arc<Instance>>;
+
+    /// 请求处理完成后回调,用于需要统计活跃请求数的策略
+    fn release(&self, _service_key: &str, _instance: &Instance) {}
+}
+
+fn instance_id(instance: &Instance) -> String {
+    format!("{}:{}", instance.ip, instance.port)
+}
+
+fn instance_weight(instance: &Instance) -> i64 {
+    (instance.weight * 1000f32) as i64
+}
+
+/// 轮询
+#[derive(Debug, Default)]
+pub struct RoundRobinBalancer {
+    index_map: Mutex<HashMap<String, usize>>,
+}
+
+impl RoundRobinBalancer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LoadBalancer for RoundRobinBalancer {
+    fn select(
+        &self,
+        service_key: &str,
+        instances: &[Arc<Instance>],
+        _hash_key: Option<&str>,
+    ) -> Option<Arc<Instance>> {
+        if instances.is_empty() {
+            return None;
+        }
+        let mut index_map = self.index_map.lock().unwrap();
+        let index = index_map.entry(service_key.to_owned()).or_default();
+        let instance = instances.get(*index % instances.len()).cloned();
+        *index = index.wrapping_add(1);
+        instance
+    }
+}
+
+/// 平滑加权轮询,与 nginx 的算法一致
+#[derive(Debug, Default)]
+pub struct SmoothWeightedBalancer {
+    weight_map: Mutex<HashMap<String, HashMap<String, i64>>>,
+}
+
+impl SmoothWeightedBalancer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LoadBalancer for SmoothWeightedBalancer {
+    fn select(
+        &self,
+        service_key: &str,
+        instances: &[Arc<Instance>],
+        _hash_key: Option<&str>,
+    ) -> Option<Arc<Instance>> {
+        let mut weight_map = self.weight_map.lock().unwrap();
+        let current_map = weight_map.entry(service_key.to_owned()).or_default();
+        let ids: HashSet<String> = instances.iter().map(|e| instance_id(e)).collect();
+        current_map.retain(|k, _| ids.contains(k));
+        let mut total = 0;
+        let mut best: Option<(&Arc<Instance>, String, i64)> = None;
+        for item in instances {
+            let weight = instance_weight(item);
+            if weight <= 0 {
+                continue;
+            }
+            let id = instance_id(item);
+            let current = current_map.entry(id.clone()).or_default();
+            *current += weight;
+            total += weight;
+            if best.as_ref().map(|e| *current > e.2).unwrap_or(true) {
+                best = Some((item, id, *current));
+            }
+        }
+        let (instance, id, _) = best?;
+        if let Some(current) = current_map.get_mut(&id) {
+            *current -= total;
+        }
+        Some(instance.clone())
+    }
+}
+
+/// 按路由键一致性哈希(加权 rendezvous hash);实例增减时只影响少量路由键。
+/// 没有路由键时按权重随机
+#[derive(Debug, Default)]
+pub struct ConsistentHashBalancer;
+
+impl ConsistentHashBalancer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn hash(hash_key: &str, id: &str) -> u64 {
+        let mut hasher = Md5::new();
+        hasher.update(hash_key.as_bytes());
+        hasher.update(b"#");
+        hasher.update(id.as_bytes());
+        let digest = hasher.finalize();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        u64::from_be_bytes(bytes)
+    }
+}
+
+impl LoadBalancer for ConsistentHashBalancer {
+    fn select(
+        &self,
+        _service_key: &str,
+        instances: &[Arc<Instance>],
+        hash_key: Option<&str>,
+    ) -> Option<Arc<Instance>> {
+        let hash_key = match hash_key {
+            Some(v) => v,
+            None => {
+                let index =
+                    NamingUtils::select_by_weight_fn(instances, |e| instance_weight(e) as u64);
+                return instances.get(index).cloned();
+            }
+        };
+        let mut best: Option<(&Arc<Instance>, f64)> = None;
+        for item in instances {
+            let weight = instance_weight(item);
+            if weight <= 0 {
+                continue;
+            }
+            // 映射到 (0,1) 区间
+            let hash = Self::hash(hash_key, &instance_id(item));
+            let u = ((hash >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+            let score = weight as f64 / -u.ln();
+            if best.as_ref().map(|e| score > e.1).unwrap_or(true) {
+                best = Some((item, score));
+            }
+        }
+        best.map(|e| e.0.clone())
+    }
+}
+
+/// 随机选两个实例,取活跃请求数(按权重折算)较少的一个;
+/// 请求完成后需要调用 `release`
+#[derive(Debug, Default)]
+pub struct P2cBalancer {
+    active_map: Mutex<HashMap<String, HashMap<String, u64>>>,
+}
+
+impl P2cBalancer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_active(&self, service_key: &str, instance: &Instance) -> u64 {
+        let active_map = self.active_map.lock().unwrap();
+        active_map
+            .get(service_key)
+            .and_then(|e| e.get(&instance_id(instance)))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl LoadBalancer for P2cBalancer {
+    fn select(
+        &self,
+        service_key: &str,
+        instances: &[Arc<Instance>],
+        _hash_key: Option<&str>,
+    ) -> Option<Arc<Instance>> {
+        let list: Vec<&Arc<Instance>> = instances
+            .iter()
+            .filter(|e| instance_weight(e) > 0)
+            .collect();
+        let mut active_map = self.active_map.lock().unwrap();
+        let service_map = active_map.entry(service_key.to_owned()).or_default();
+        let instance = match list.len() {
+            0 => return None,
+            1 => list[0],
+            len => {
+                let mut rng = rand::thread_rng();
+                let a = rng.gen_range(0..len);
+                let b = (a + rng.gen_range(1..len)) % len;
+                let load = |e: &Instance| {
+                    let active = service_map
+                        .get(&instance_id(e))
+                        .copied()
+                        .unwrap_or_default();
+                    (active + 1) as f64 / instance_weight(e) as f64
+                };
+                if load(list[a]) <= load(list[b]) {
+                    list[a]
+                } else {
+                    list[b]
+                }
+            }
+        };
+        *service_map.entry(instance_id(instance)).or_default() += 1;
+        Some(instance.clone())
+    }
+
+    fn release(&self, service_key: &str, instance: &Instance) {
+        let mut active_map = self.active_map.lock().unwrap();
+        if let Some(active) = active_map
+            .get_mut(service_key)
+            .and_then(|e| e.get_mut(&instance_id(instance)))
+        {
+            *active = active.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_instances(weights: &[f32]) -> Vec<Arc<Instance>> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let mut instance = Instance::new_simple("127.0.0.1", 8000 + i as u32, "foo", "");
+                instance.weight = *w;
+                Arc::new(instance)
+            })
+            .collect()
+    }
+
+    fn select_ports(
+        balancer: &dyn LoadBalancer,
+        instances: &[Arc<Instance>],
+        n: usize,
+    ) -> Vec<u32> {
+        (0..n)
+            .map(|_| balancer.select("foo", instances, None).unwrap().port)
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let instances = build_instances(&[1f32, 1f32, 1f32]);
+        let balancer = RoundRobinBalancer::new();
+        assert_eq!(
+            select_ports(&balancer, &instances, 4),
+            vec![8000, 8001, 8002, 8000]
+        );
+    }
+
+    #[test]
+    fn test_smooth_weighted() {
+        let instances = build_instances(&[5f32, 1f32, 1f32]);
+        let balancer = SmoothWeightedBalancer::new();
+        assert_eq!(
+            select_ports(&balancer, &instances, 7),
+            vec![8000, 8000, 8001, 8000, 8002, 8000, 8000]
+        );
+    }
+
+    #[test]
+    fn test_consistent_hash() {
+        let instances = build_instances(&[1f32, 1f32, 1f32, 1f32]);
+        let balancer = ConsistentHashBalancer::new();
+        for i in 0..20 {
+            let key = format!("user_{}", i);
+            let one = balancer.select("foo", &instances, Some(&key)).unwrap();
+            let two = balancer.select("foo", &instances, Some(&key)).unwrap();
+            assert_eq!(one.port, two.port);
+            // 移除其它实例不影响已路由到的实例
+            let remain: Vec<Arc<Instance>> = instances
+                .iter()
+                .filter(|e| e.port == one.port || e.port % 2 == 0)
+                .cloned()
+                .collect();
+            let three = balancer.select("foo", &remain, Some(&key)).unwrap();
+            assert_eq!(one.port, three.port);
+        }
+    }
+
+    #[test]
+    fn test_p2c() {
+        let instances = build_instances(&[1f32, 1f32]);
+        let balancer = P2cBalancer::new();
+        let one = balancer.select("foo", &instances, None).unwrap();
+        let two = balancer.select("foo", &instances, None).unwrap();
+        assert_ne!(one.port, two.port);
+        balancer.release("foo", &one);
+        assert_eq!(balancer.get_active("foo", &one), 0);
+        assert_eq!(balancer.get_active("foo", &two), 1);
+    }
+}
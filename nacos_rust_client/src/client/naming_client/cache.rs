@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::Instance;
+use crate::client::now_millis;
+use crate::client::utils::Utils;
+use crate::conn_manage::conn_msg::ServiceResult;
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ServiceCacheData {
+    hosts: Vec<Instance>,
+    cache_millis: Option<u64>,
+    protect_threshold: Option<f32>,
+    #[serde(default)]
+    reach_protection_threshold: bool,
+    last_ref_time: u64,
+}
+
+/// 服务实例本地缓存,服务端不可用时作为兜底
+#[derive(Debug, Clone)]
+pub struct NamingCache {
+    dir: PathBuf,
+}
+
+impl NamingCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn build_namespace_dir(&self, namespace_id: &str) -> PathBuf {
+        let namespace_id = if namespace_id.is_empty() {
+            "public"
+        } else {
+            namespace_id
+        };
+        self.dir.join(Utils::encode_path_segment(namespace_id))
+    }
+
+    /// key 为 group@@service_name
+    pub fn write(
+        &self,
+        namespace_id: &str,
+        key: &str,
+        result: &ServiceResult,
+    ) -> anyhow::Result<()> {
+        let data = ServiceCacheData {
+            hosts: result.hosts.iter().map(|e| e.as_ref().clone()).collect(),
+            cache_millis: result.cache_millis,
+            protect_threshold: result.protect_threshold,
+            reach_protection_threshold: result.reach_protection_threshold,
+            last_ref_time: now_millis(),
+        };
+        let path = self
+            .build_namespace_dir(namespace_id)
+            .join(Utils::encode_path_segment(key));
+        Utils::write_file_atomic(&path, &serde_json::to_vec(&data)?)
+    }
+
+    pub fn read(&self, namespace_id: &str, key: &str) -> Option<ServiceResult> {
+        let path = self
+            .build_namespace_dir(namespace_id)
+            .join(Utils::encode_path_segment(key));
+        Self::read_file(&path)
+    }
+
+    fn read_file(path: &Path) -> Option<ServiceResult> {
+        let data = std::fs::read(path).ok()?;
+        let data: ServiceCacheData = serde_json::from_slice(&data).ok()?;
+        Some(ServiceResult {
+            hosts: data.hosts.into_iter().map(Arc::new).collect(),
+            cache_millis: data.cache_millis,
+            protect_threshold: data.protect_threshold,
+            reach_protection_threshold: data.reach_protection_threshold,
+        })
+    }
+
+    /// 加载命名空间下的全部缓存,返回 (group@@service_name, ServiceResult)
+    pub fn load_all(&self, namespace_id: &str) -> Vec<(String, ServiceResult)> {
+        let mut list = vec![];
+        let dir = match std::fs::read_dir(self.build_namespace_dir(namespace_id)) {
+            Ok(dir) => dir,
+            Err(_) => return list,
+        };
+        for entry in dir.flatten() {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            let key = match path.file_name().and_then(|e| e.to_str()) {
+                Some(name) if !name.ends_with(".tmp") => Utils::decode_path_segment(name),
+                _ => continue,
+            };
+            if let Some(result) = Self::read_file(&path) {
+                list.push((key, result));
+            }
+        }
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_write_load() {
+        let dir = std::env::temp_dir().join(format!("nacos_naming_cache_test_{}", now_millis()));
+        let cache = NamingCache::new(&dir);
+        let key = "DEFAULT_GROUP@@foo/bar";
+        let instance = Instance::new_simple("127.0.0.1", 8080, "foo/bar", "");
+        let result = ServiceResult {
+            hosts: vec![Arc::new(instance)],
+            cache_millis: Some(1000),
+            ..Default::default()
+        };
+        cache.write("", key, &result).unwrap();
+        let v = cache.read("public", key).unwrap();
+        assert_eq!(v.hosts.len(), 1);
+        assert_eq!(v.hosts[0].port, 8080);
+        let list = cache.load_all("public");
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].0, key);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
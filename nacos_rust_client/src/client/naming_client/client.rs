@@ -2,15 +2,19 @@ use crate::client::auth::AuthActor;
 use crate::client::nacos_client::ActixSystemActorSetCmd;
 use crate::client::nacos_client::ActixSystemCmd;
 use crate::client::nacos_client::ActixSystemResult;
+use crate::client::nacos_client::ActorRuntime;
 use crate::client::AuthInfo;
 use crate::client::ServerEndpointInfo;
-use crate::conn_manage::manage::ConnManage;
+use crate::conn_manage::manage::{ConnManage, ConnManageCmd};
 use crate::init_global_system_actor;
 use std::env;
 use std::sync::Arc;
 
 use super::Instance;
 use super::InstanceListener;
+use super::LoadBalancer;
+use super::NamingCache;
+use super::NamingProtectPolicy;
 use super::NamingQueryCmd;
 use super::NamingQueryResult;
 use super::QueryInstanceListParams;
@@ -20,6 +24,7 @@ use super::{
     NamingRegisterCmd, UdpWorker,
 };
 use crate::client::HostInfo;
+use crate::error::{Error, Result};
 use actix::prelude::*;
 use actix::WeakAddr;
 
@@ -45,7 +50,10 @@ impl NamingClient {
             Ok(v) => v,
             Err(_) => local_ipaddress::get().unwrap_or("127.0.0.1".to_owned()),
         };
-        let endpoint = Arc::new(ServerEndpointInfo { hosts: vec![host] });
+        let endpoint = Arc::new(ServerEndpointInfo {
+            hosts: vec![host],
+            ..Default::default()
+        });
         let auth_actor = AuthActor::init_auth_actor(endpoint.clone(), None);
         let conn_manage = ConnManage::new(
             endpoint.hosts.clone(),
@@ -64,6 +72,7 @@ impl NamingClient {
             request_client,
             Some(conn_manage_addr.clone().downgrade()),
             use_grpc,
+            None,
         );
         let r = Arc::new(Self {
             namespace_id,
@@ -106,6 +115,7 @@ impl NamingClient {
             request_client,
             Some(conn_manage_addr.clone().downgrade()),
             use_grpc,
+            None,
         );
         let r = Arc::new(Self {
             namespace_id,
@@ -125,6 +135,7 @@ impl NamingClient {
         request_client: InnerNamingRequestClient,
         conn_manage_addr: Option<WeakAddr<ConnManage>>,
         use_grpc: bool,
+        cache: Option<Arc<NamingCache>>,
     ) -> (Addr<InnerNamingRegister>, Addr<InnerNamingListener>) {
         let system_addr = init_global_system_actor();
 
@@ -154,7 +165,8 @@ impl NamingClient {
             udp_work_addr,
             conn_manage_addr,
             use_grpc,
-        );
+        )
+        .set_naming_cache(cache);
         let (tx, rx) = std::sync::mpsc::sync_channel(1);
         let msg = ActixSystemCmd::InnerNamingListener(actor, tx);
         system_addr.do_send(msg);
@@ -165,12 +177,62 @@ impl NamingClient {
         (register_addr, listener_addr)
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn init_register_at(
+        runtime: &ActorRuntime,
+        namespace_id: String,
+        client_ip: String,
+        request_client: InnerNamingRequestClient,
+        conn_manage_addr: Option<WeakAddr<ConnManage>>,
+        use_grpc: bool,
+        cache: Option<Arc<NamingCache>>,
+        protect_policy: NamingProtectPolicy,
+    ) -> (Addr<InnerNamingRegister>, Addr<InnerNamingListener>) {
+        let actor = InnerNamingRegister::new(use_grpc, conn_manage_addr.clone());
+        let register_addr = runtime.start(actor);
+        let udp_work_addr = runtime.start(UdpWorker::new(None));
+        let actor = InnerNamingListener::new(
+            &namespace_id,
+            &client_ip,
+            0,
+            request_client,
+            udp_work_addr,
+            conn_manage_addr,
+            use_grpc,
+        )
+        .set_naming_cache(cache)
+        .set_protect_policy(protect_policy);
+        let listener_addr = runtime.start(actor);
+        (register_addr, listener_addr)
+    }
+
     pub(crate) fn droping(&self) {
         log::info!("NamingClient droping");
         self.register.do_send(NamingRegisterCmd::Close);
         self.listener_addr.do_send(NamingListenerCmd::Close);
     }
 
+    /// 设置实例推送保护策略
+    pub fn set_protect_policy(&self, policy: NamingProtectPolicy) {
+        self.listener_addr
+            .do_send(NamingListenerCmd::SetProtectPolicy(policy));
+    }
+
+    /// 注销本客户端注册的临时实例,取消订阅并关闭连接;完成后客户端不可再使用
+    pub async fn shutdown(&self) -> Result<()> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.register.do_send(NamingRegisterCmd::Shutdown(tx));
+        let register_result = rx.await.unwrap_or(Ok(()));
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.listener_addr.do_send(NamingListenerCmd::Shutdown(tx));
+        let listener_result = rx.await.unwrap_or(Ok(()));
+        self._conn_manage_addr
+            .send(ConnManageCmd::DetachNamingClient)
+            .await
+            .ok();
+        Ok(register_result.and(listener_result)?)
+    }
+
     pub fn register(&self, mut instance: Instance) {
         instance.namespace_id = self.namespace_id.clone();
         self.register.do_send(NamingRegisterCmd::Register(instance));
@@ -181,38 +243,79 @@ impl NamingClient {
         self.register.do_send(NamingRegisterCmd::Remove(instance));
     }
 
+    /// 注册实例并等待服务端确认;失败时后台仍会继续尝试注册
+    pub async fn register_async(&self, mut instance: Instance) -> Result<()> {
+        instance.namespace_id = self.namespace_id.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.register
+            .do_send(NamingRegisterCmd::RegisterWithResult(instance, tx));
+        Ok(rx.await??)
+    }
+
+    /// 注销实例并等待服务端确认
+    pub async fn unregister_async(&self, mut instance: Instance) -> Result<()> {
+        instance.namespace_id = self.namespace_id.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.register
+            .do_send(NamingRegisterCmd::RemoveWithResult(instance, tx));
+        Ok(rx.await??)
+    }
+
+    /// 更新实例的权重、上下线状态与元数据,不会先注销实例;实例需先通过本客户端注册,否则返回 NotFound
+    pub async fn update_instance(&self, mut instance: Instance) -> Result<()> {
+        instance.namespace_id = self.namespace_id.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.register
+            .do_send(NamingRegisterCmd::UpdateWithResult(instance, tx));
+        Ok(rx.await??)
+    }
+
     pub async fn query_instances(
         &self,
         mut params: QueryInstanceListParams,
-    ) -> anyhow::Result<Vec<Arc<Instance>>> {
+    ) -> Result<Vec<Arc<Instance>>> {
         params.namespace_id = self.namespace_id.clone();
         let (tx, rx) = tokio::sync::oneshot::channel();
         self.listener_addr
             .do_send(NamingQueryCmd::QueryList(params, tx));
         match rx.await? {
             NamingQueryResult::List(list) => Ok(list),
-            _ => Err(anyhow::anyhow!("not found instance")),
+            _ => Err(Error::NotFound("not found instance".to_owned())),
         }
     }
 
     pub async fn select_instance(
         &self,
         mut params: QueryInstanceListParams,
-    ) -> anyhow::Result<Arc<Instance>> {
+    ) -> Result<Arc<Instance>> {
         params.namespace_id = self.namespace_id.clone();
         let (tx, rx) = tokio::sync::oneshot::channel();
         self.listener_addr
             .do_send(NamingQueryCmd::Select(params, tx));
         match rx.await? {
             NamingQueryResult::One(one) => Ok(one),
-            _ => Err(anyhow::anyhow!("not found instance")),
+            _ => Err(Error::NotFound("not found instance".to_owned())),
         }
     }
 
+    /// 使用指定的负载均衡策略选择实例;hash_key 为一致性哈希的路由键
+    pub async fn select_instance_with(
+        &self,
+        params: QueryInstanceListParams,
+        balancer: &dyn LoadBalancer,
+        hash_key: Option<&str>,
+    ) -> Result<Arc<Instance>> {
+        let service_key = params.get_key();
+        let list = self.query_instances(params).await?;
+        balancer
+            .select(&service_key, &list, hash_key)
+            .ok_or_else(|| Error::NotFound("not found instance".to_owned()))
+    }
+
     pub async fn subscribe<T: InstanceListener + Send + 'static>(
         &self,
         listener: Box<T>,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let key = listener.get_key();
         self.subscribe_with_key(key, listener).await
     }
@@ -221,7 +324,7 @@ impl NamingClient {
         &self,
         key: ServiceInstanceKey,
         listener: Box<T>,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let id = 0u64;
         //如果之前没有数据，会触发加载数据
         let params = QueryInstanceListParams::new(
@@ -237,7 +340,7 @@ impl NamingClient {
         Ok(())
     }
 
-    pub async fn unsubscribe(&self, key: ServiceInstanceKey) -> anyhow::Result<()> {
+    pub async fn unsubscribe(&self, key: ServiceInstanceKey) -> Result<()> {
         let id = 0u64;
         let msg = NamingListenerCmd::Remove(key, id);
         self.listener_addr.do_send(msg);
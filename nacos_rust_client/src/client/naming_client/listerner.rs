@@ -18,6 +18,7 @@ use inner_mem_cache::TimeoutSet;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use super::cache::NamingCache;
 use super::udp_actor::{InitLocalAddr, UdpWorkerCmd};
 use super::{Instance, QueryInstanceListParams};
 
@@ -110,6 +111,24 @@ impl ListenerValue {
     }
 }
 
+/// 实例推送保护策略,默认不开启
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NamingProtectPolicy {
+    /// 忽略空实例列表的推送,保留上一次的实例列表
+    pub ignore_empty_push: bool,
+    /// 健康实例占比低于服务的 protect_threshold(gRPC 为服务端返回的 reachProtectionThreshold)时,
+    /// 只查健康实例也返回全部实例
+    pub use_protect_threshold: bool,
+}
+
+/// 查询健康实例时使用的保护阈值
+#[derive(Debug, Default, Clone, Copy)]
+struct ProtectThreshold {
+    threshold: f32,
+    //服务端已判定达到保护阈值
+    reached: bool,
+}
+
 #[derive(Debug, Default, Clone)]
 struct InstancesWrap {
     instances: Vec<Arc<Instance>>,
@@ -117,6 +136,10 @@ struct InstancesWrap {
     //last_sign:String,
     next_time: u64,
     //empty_times:u8,
+    //最近一次从服务端更新的时间,0表示当前实例来自本地缓存
+    update_time: u64,
+    protect_threshold: f32,
+    reach_protection_threshold: bool,
 }
 
 pub struct InnerNamingListener {
@@ -132,6 +155,10 @@ pub struct InnerNamingListener {
     udp_port: u16,
     udp_addr: Addr<UdpWorker>,
     use_grpc: bool,
+    cache: Option<Arc<NamingCache>>,
+    //启动时从本地缓存加载的实例,订阅时作为初始值
+    cache_instances: HashMap<String, Vec<Arc<Instance>>>,
+    protect_policy: NamingProtectPolicy,
 }
 
 impl InnerNamingListener {
@@ -156,6 +183,31 @@ impl InnerNamingListener {
             udp_port,
             udp_addr,
             use_grpc,
+            cache: None,
+            cache_instances: Default::default(),
+            protect_policy: Default::default(),
+        }
+    }
+
+    pub fn set_naming_cache(mut self, cache: Option<Arc<NamingCache>>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn set_protect_policy(mut self, policy: NamingProtectPolicy) -> Self {
+        self.protect_policy = policy;
+        self
+    }
+
+    fn load_cache(&mut self) {
+        if let Some(cache) = &self.cache {
+            for (key, result) in cache.load_all(&self.namespace_id) {
+                self.cache_instances.insert(key, result.hosts);
+            }
+            log::info!(
+                "InnerNamingListener load {} services from local cache",
+                self.cache_instances.len()
+            );
         }
     }
 
@@ -204,17 +256,35 @@ impl InnerNamingListener {
         let mut is_notify = false;
         let mut old_instance_map = HashMap::new();
         if let Some(instance_warp) = self.instances.get_mut(&key) {
-            for e in &instance_warp.instances {
-                old_instance_map.insert(format!("{}:{}", e.ip, e.port), e.clone());
-            }
-            instance_warp.instances = result
+            let hosts: Vec<Arc<Instance>> = result
                 .hosts
-                .into_iter()
+                .iter()
                 .filter(|e| e.weight > 0.001f32)
+                .cloned()
                 .collect();
-            is_notify = true;
             let current_time = now_millis();
+            if hosts.is_empty()
+                && !instance_warp.instances.is_empty()
+                && self.protect_policy.ignore_empty_push
+            {
+                log::warn!("ignore empty instance push,{}", &key);
+                instance_warp.next_time = current_time + self.period;
+                return Ok(());
+            }
+            for e in &instance_warp.instances {
+                old_instance_map.insert(format!("{}:{}", e.ip, e.port), e.clone());
+            }
+            if let Some(cache) = &self.cache {
+                if let Err(err) = cache.write(&self.namespace_id, &key, &result) {
+                    log::warn!("write naming cache error,{},{}", &key, err);
+                }
+            }
+            instance_warp.instances = hosts;
+            instance_warp.protect_threshold = result.protect_threshold.unwrap_or_default();
+            instance_warp.reach_protection_threshold = result.reach_protection_threshold;
+            is_notify = true;
             instance_warp.next_time = current_time + self.period;
+            instance_warp.update_time = current_time;
         }
         if is_notify {
             if let Some(instance_warp) = self.instances.get(&key) {
@@ -251,6 +321,8 @@ impl InnerNamingListener {
         ServiceResult {
             cache_millis: result.cache_millis,
             hosts,
+            protect_threshold: result.protect_threshold,
+            ..Default::default()
         }
     }
 
@@ -311,20 +383,12 @@ impl InnerNamingListener {
     ) -> Option<Vec<Arc<Instance>>> {
         let key = params.get_key();
         if let Some(instance_warp) = self.instances.get(&key) {
-            let mut list = vec![];
-            for item in &instance_warp.instances {
-                if params.healthy_only && !item.healthy {
-                    continue;
-                }
-                if let Some(clusters) = &params.clusters {
-                    let name = &item.cluster_name;
-                    if !clusters.contains(name) {
-                        continue;
-                    }
-                }
-                list.push(item.clone());
-            }
-            return Some(list);
+            let protect = self.get_protect_threshold(instance_warp);
+            return Some(Self::do_filter_instances(
+                params,
+                &instance_warp.instances,
+                protect,
+            ));
             //if list.len()> 0 {
             //    return Some(list);
             //}
@@ -335,6 +399,101 @@ impl InnerNamingListener {
         None
     }
 
+    fn get_protect_threshold(&self, instance_warp: &InstancesWrap) -> ProtectThreshold {
+        if self.protect_policy.use_protect_threshold {
+            ProtectThreshold {
+                threshold: instance_warp.protect_threshold,
+                reached: instance_warp.reach_protection_threshold,
+            }
+        } else {
+            Default::default()
+        }
+    }
+
+    fn do_filter_instances(
+        params: &QueryInstanceListParams,
+        instances: &[Arc<Instance>],
+        protect: ProtectThreshold,
+    ) -> Vec<Arc<Instance>> {
+        let empty_metadata = HashMap::new();
+        let mut list = vec![];
+        for item in instances {
+            if let Some(clusters) = &params.clusters {
+                let name = &item.cluster_name;
+                if !clusters.contains(name) {
+                    continue;
+                }
+            }
+            if let Some(selector) = &params.selector {
+                if !selector.matches(item.metadata.as_ref().unwrap_or(&empty_metadata)) {
+                    continue;
+                }
+            }
+            list.push(item.clone());
+        }
+        let list = Self::filter_healthy_instances(params, list, protect);
+        if let Some(labels) = &params.affinity_labels {
+            let affinity_list: Vec<Arc<Instance>> = list
+                .iter()
+                .filter(|e| {
+                    e.healthy
+                        && labels
+                            .iter()
+                            .all(|(k, v)| e.metadata.as_ref().and_then(|m| m.get(k)) == Some(v))
+                })
+                .cloned()
+                .collect();
+            if !affinity_list.is_empty() {
+                return affinity_list;
+            }
+        }
+        list
+    }
+
+    fn filter_healthy_instances(
+        params: &QueryInstanceListParams,
+        list: Vec<Arc<Instance>>,
+        protect: ProtectThreshold,
+    ) -> Vec<Arc<Instance>> {
+        if !params.healthy_only {
+            return list;
+        }
+        let healthy_list: Vec<Arc<Instance>> = list.iter().filter(|e| e.healthy).cloned().collect();
+        if protect.reached
+            || (protect.threshold > 0f32
+                && !list.is_empty()
+                && (healthy_list.len() as f32) / (list.len() as f32) < protect.threshold)
+        {
+            log::warn!(
+                "healthy instance ratio is lower than protect threshold,{}@@{}",
+                &params.group_name,
+                &params.service_name
+            );
+            return list;
+        }
+        healthy_list
+    }
+
+    /// 请求服务端失败时,使用本地缓存的实例
+    fn filter_cache_instances(
+        &self,
+        params: &QueryInstanceListParams,
+    ) -> Option<Vec<Arc<Instance>>> {
+        let key = params.get_key();
+        let instances = match self.instances.get(&key) {
+            Some(instance_warp) if instance_warp.update_time == 0 => &instance_warp.instances,
+            Some(_) => return None,
+            None => self.cache_instances.get(&key)?,
+        };
+        let list = Self::do_filter_instances(params, instances, Default::default());
+        if list.is_empty() {
+            None
+        } else {
+            log::warn!("query instance error,use local cache,{}", &key);
+            Some(list)
+        }
+    }
+
     pub fn hb(&self, ctx: &mut actix::Context<Self>) {
         ctx.run_later(Duration::new(1, 0), |act, ctx| {
             let current_time = now_millis();
@@ -381,6 +540,7 @@ impl Actor for InnerNamingListener {
                 ));
             }
         }
+        self.load_cache();
         if !self.use_grpc {
             self.init_udp_info(ctx);
             self.hb(ctx);
@@ -400,7 +560,10 @@ pub enum NamingListenerCmd {
     AddHeartbeat(ServiceInstanceKey),
     Heartbeat(String, u64),
     Close,
+    /// 取消全部订阅后停止
+    Shutdown(tokio::sync::oneshot::Sender<anyhow::Result<()>>),
     GrpcResubscribe,
+    SetProtectPolicy(NamingProtectPolicy),
 }
 
 impl Handler<NamingListenerCmd> for InnerNamingListener {
@@ -446,6 +609,9 @@ impl Handler<NamingListenerCmd> for InnerNamingListener {
                     instances.params.client_ip = Some(self.client_ip.clone());
                     instances.params.udp_port = Some(self.udp_port);
                     instances.next_time = current_time;
+                    if let Some(cache_instances) = self.cache_instances.remove(&key_str) {
+                        instances.instances = cache_instances;
+                    }
                     self.instances.insert(key_str.clone(), instances);
                     if self.use_grpc {
                         let request = NamingRequest::Subscribe(vec![clone_key]);
@@ -500,9 +666,35 @@ impl Handler<NamingListenerCmd> for InnerNamingListener {
                 log::info!("InnerNamingListener close");
                 ctx.stop();
             }
+            NamingListenerCmd::Shutdown(sender) => {
+                self.udp_addr.do_send(UdpWorkerCmd::Close);
+                let keys: Vec<ServiceInstanceKey> = self
+                    .listeners
+                    .drain()
+                    .map(|(k, _)| k.as_str().into())
+                    .collect();
+                let conn_manage = self.conn_manage.clone();
+                let use_grpc = self.use_grpc;
+                async move {
+                    if use_grpc && !keys.is_empty() {
+                        Self::send_conn_msg(conn_manage, NamingRequest::Unsubscribe(keys)).await?;
+                    }
+                    Ok(())
+                }
+                .into_actor(self)
+                .map(|res: anyhow::Result<()>, _, ctx| {
+                    log::info!("InnerNamingListener shutdown");
+                    sender.send(res).ok();
+                    ctx.stop();
+                })
+                .wait(ctx);
+            }
             NamingListenerCmd::GrpcResubscribe => {
                 self.grpc_resubscribe();
             }
+            NamingListenerCmd::SetProtectPolicy(policy) => {
+                self.protect_policy = policy;
+            }
         };
         Ok(())
     }
@@ -608,6 +800,11 @@ impl Handler<NamingQueryCmd> for InnerNamingListener {
                                     .unwrap_or_default();
                                 return;
                             }
+                        } else if let Some(list) = act.filter_cache_instances(&param) {
+                            sender
+                                .send(NamingQueryResult::List(list))
+                                .unwrap_or_default();
+                            return;
                         }
                         sender.send(NamingQueryResult::None).unwrap_or_default();
                     })
@@ -660,6 +857,16 @@ impl Handler<NamingQueryCmd> for InnerNamingListener {
                                     return;
                                 }
                             }
+                        } else if let Some(list) = act.filter_cache_instances(&param) {
+                            let index = NamingUtils::select_by_weight_fn(&list, |e| {
+                                (e.weight * 1000f32) as u64
+                            });
+                            if let Some(e) = list.get(index) {
+                                sender
+                                    .send(NamingQueryResult::One(e.clone()))
+                                    .unwrap_or_default();
+                                return;
+                            }
                         }
                         sender.send(NamingQueryResult::None).unwrap_or_default();
                     })
@@ -676,3 +883,76 @@ impl Handler<NamingQueryCmd> for InnerNamingListener {
         Ok(NamingQueryResult::None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::naming_client::LabelSelector;
+
+    #[test]
+    fn test_filter_instances_protect_threshold() {
+        let mut instances = vec![];
+        for i in 0..4 {
+            let mut instance = Instance::new_simple("127.0.0.1", 8000 + i, "foo", "");
+            instance.healthy = i == 0;
+            instances.push(Arc::new(instance));
+        }
+        let mut params = QueryInstanceListParams::new_simple("foo", "");
+        params.healthy_only = true;
+        let list =
+            InnerNamingListener::do_filter_instances(&params, &instances, Default::default());
+        assert_eq!(list.len(), 1);
+        let list = InnerNamingListener::do_filter_instances(
+            &params,
+            &instances,
+            ProtectThreshold {
+                threshold: 0.5,
+                reached: false,
+            },
+        );
+        assert_eq!(list.len(), 4);
+        let list = InnerNamingListener::do_filter_instances(
+            &params,
+            &instances,
+            ProtectThreshold {
+                threshold: 0.2,
+                reached: false,
+            },
+        );
+        assert_eq!(list.len(), 1);
+        //gRPC 由服务端判定是否达到保护阈值
+        let protect = ProtectThreshold {
+            threshold: 0f32,
+            reached: true,
+        };
+        let list = InnerNamingListener::do_filter_instances(&params, &instances, protect);
+        assert_eq!(list.len(), 4);
+    }
+
+    #[test]
+    fn test_filter_instances_labels() {
+        let mut instances = vec![];
+        for (i, zone) in ["a", "a", "b"].iter().enumerate() {
+            let mut instance = Instance::new_simple("127.0.0.1", 8000 + i as u32, "foo", "");
+            let mut metadata = HashMap::new();
+            metadata.insert("zone".to_owned(), zone.to_string());
+            instance.metadata = Some(metadata);
+            instance.healthy = i != 0;
+            instances.push(Arc::new(instance));
+        }
+        let mut labels = HashMap::new();
+        labels.insert("zone".to_owned(), "a".to_owned());
+        let params = QueryInstanceListParams::new("", "", "foo", None, false)
+            .set_selector(Some(LabelSelector::parse("zone in (a,b)").unwrap()))
+            .set_affinity_labels(Some(labels.clone()));
+        let list =
+            InnerNamingListener::do_filter_instances(&params, &instances, Default::default());
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].port, 8001);
+        let params = params.set_selector(Some(LabelSelector::parse("zone=b").unwrap()));
+        let list =
+            InnerNamingListener::do_filter_instances(&params, &instances, Default::default());
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].port, 8002);
+    }
+}
@@ -3,13 +3,17 @@ use std::sync::Arc;
 use std::time::Duration;
 //use actix::prelude::*;
 use inner_mem_cache::TimeoutSet;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 mod api_model;
+mod balancer;
+mod cache;
 mod client;
 mod listerner;
 mod register;
 mod request_client;
+mod selector;
 mod udp_actor;
 
 pub use request_client::InnerNamingRequestClient;
@@ -18,17 +22,23 @@ pub use api_model::{
     BeatInfo, BeatRequest, InstanceVO, InstanceWebParams, InstanceWebQueryListParams, NamingUtils,
     QueryListResult,
 };
+pub use balancer::{
+    ConsistentHashBalancer, LoadBalancer, P2cBalancer, RoundRobinBalancer, SmoothWeightedBalancer,
+};
+pub use cache::NamingCache;
 pub use client::NamingClient;
 pub use listerner::{
     InnerNamingListener, InstanceDefaultListener, InstanceListener, NamingListenerCmd,
-    NamingQueryCmd, NamingQueryResult,
+    NamingProtectPolicy, NamingQueryCmd, NamingQueryResult,
 };
 pub use register::{InnerNamingRegister, NamingRegisterCmd};
+pub use selector::LabelSelector;
 pub use udp_actor::{UdpDataCmd, UdpWorker};
 
 pub(crate) static REGISTER_PERIOD: u64 = 5000u64;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct Instance {
     //pub id:String,
     pub ip: String,
@@ -43,6 +53,7 @@ pub struct Instance {
     pub metadata: Option<HashMap<String, String>>,
     pub namespace_id: String,
     //pub app_name:String,
+    #[serde(skip)]
     pub beat_string: Option<Arc<String>>,
 }
 
@@ -91,6 +102,12 @@ impl Instance {
         }
     }
 
+    /// 设置为持久化实例(ephemeral=false)后,注册时不维持心跳,客户端关闭时也不会注销
+    pub fn set_ephemeral(mut self, ephemeral: bool) -> Self {
+        self.ephemeral = ephemeral;
+        self
+    }
+
     pub fn generate_key(&self) -> String {
         format!(
             "{}#{}#{}#{}#{}#{}",
@@ -147,9 +164,9 @@ impl Instance {
             port: self.port,
             namespace_id: self.namespace_id.to_owned(),
             weight: self.weight,
-            enabled: true,
+            enabled: self.enabled,
             healthy: true,
-            ephemeral: true,
+            ephemeral: self.ephemeral,
             metadata: self
                 .metadata
                 .as_ref()
@@ -214,6 +231,10 @@ pub struct QueryInstanceListParams {
     pub service_name: String,
     pub clusters: Option<Vec<String>>,
     pub healthy_only: bool,
+    /// 按实例元数据过滤
+    pub selector: Option<LabelSelector>,
+    /// 优先选择元数据包含这些标签的健康实例,没有时再使用其它实例
+    pub affinity_labels: Option<HashMap<String, String>>,
     client_ip: Option<String>,
     udp_port: Option<u16>,
 }
@@ -232,11 +253,23 @@ impl QueryInstanceListParams {
             service_name: service_name.to_owned(),
             clusters,
             healthy_only,
+            selector: None,
+            affinity_labels: None,
             client_ip: None,
             udp_port: None,
         }
     }
 
+    pub fn set_selector(mut self, selector: Option<LabelSelector>) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    pub fn set_affinity_labels(mut self, labels: Option<HashMap<String, String>>) -> Self {
+        self.affinity_labels = labels;
+        self
+    }
+
     pub fn new_simple(service_name: &str, group_name: &str) -> Self {
         Self::new("", group_name, service_name, None, true)
     }
@@ -3,6 +3,7 @@ use crate::client::now_millis;
 use crate::conn_manage::conn_msg::NamingRequest;
 use crate::conn_manage::manage::ConnManage;
 use crate::conn_manage::manage::ConnManageCmd;
+use crate::error::Error;
 use actix::prelude::*;
 use actix::WeakAddr;
 use std::collections::HashMap;
@@ -58,7 +59,10 @@ impl InnerNamingRegister {
     fn remove_all_instance(&mut self, ctx: &mut actix::Context<Self>) {
         let instances = self.instances.clone();
         for (_, instance) in instances {
-            self.remove_instance(instance, ctx);
+            //持久化实例不随客户端关闭注销
+            if instance.ephemeral {
+                self.remove_instance(instance, ctx);
+            }
         }
         self.instances = HashMap::new();
     }
@@ -71,6 +75,110 @@ impl InnerNamingRegister {
         }
     }
 
+    fn request_with_result(
+        &self,
+        request: NamingRequest,
+        sender: RegisterResultSender,
+        ctx: &mut actix::Context<Self>,
+    ) {
+        Self::send_request(self.conn_manage.clone(), request)
+            .into_actor(self)
+            .map(|res, _, _| {
+                sender.send(res).ok();
+            })
+            .spawn(ctx);
+    }
+
+    async fn send_request(
+        conn_manage: Option<WeakAddr<ConnManage>>,
+        request: NamingRequest,
+    ) -> anyhow::Result<()> {
+        if let Some(Some(addr)) = conn_manage.map(|e| e.upgrade()) {
+            addr.send(request).await??;
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("conn manage is empty"))
+        }
+    }
+
+    /// 只更新已注册的实例;服务端确认后才替换本地记录,避免心跳与重新注册使用未生效的数据
+    fn update_instance(
+        &mut self,
+        mut instance: Instance,
+        sender: RegisterResultSender,
+        ctx: &mut actix::Context<Self>,
+    ) {
+        let key = instance.generate_key();
+        if !self.instances.contains_key(&key) {
+            let err = Error::NotFound(format!("instance is not registered,{}", &key));
+            sender.send(Err(err.into())).ok();
+            return;
+        }
+        instance.init_beat_string();
+        let request = NamingRequest::Update(instance.clone());
+        Self::send_request(self.conn_manage.clone(), request)
+            .into_actor(self)
+            .map(move |res, act, _| {
+                if res.is_ok() {
+                    if let Some(old) = act.instances.get_mut(&key) {
+                        *old = instance;
+                    }
+                }
+                sender.send(res).ok();
+            })
+            .spawn(ctx);
+    }
+
+    fn shutdown(&mut self, sender: RegisterResultSender, ctx: &mut actix::Context<Self>) {
+        self.stop_remove_all = true;
+        let instances: Vec<Instance> = self
+            .instances
+            .drain()
+            .map(|(_, v)| v)
+            .filter(|e| e.ephemeral)
+            .collect();
+        let conn_manage = self.conn_manage.clone();
+        async move {
+            let mut result = Ok(());
+            if let Some(Some(addr)) = conn_manage.map(|e| e.upgrade()) {
+                for instance in instances {
+                    let key = instance.generate_key();
+                    let res = match addr.send(NamingRequest::Unregister(instance)).await {
+                        Ok(res) => res.map(|_| ()),
+                        Err(err) => Err(err.into()),
+                    };
+                    if let Err(err) = res {
+                        log::warn!("shutdown unregister instance error,{},{}", &key, &err);
+                        result = Err(err);
+                    }
+                }
+            }
+            result
+        }
+        .into_actor(self)
+        .map(|res: anyhow::Result<()>, _, ctx| {
+            sender.send(res).ok();
+            ctx.stop();
+        })
+        .wait(ctx);
+    }
+
+    /// 记录实例,用于维持心跳与重连后重新注册;返回是否为新实例
+    fn add_instance(&mut self, mut instance: Instance) -> bool {
+        instance.init_beat_string();
+        let key = instance.generate_key();
+        if self.instances.contains_key(&key) {
+            return false;
+        }
+        let ephemeral = instance.ephemeral;
+        self.instances.insert(key.clone(), instance);
+        if !self.use_grpc && ephemeral {
+            let time = now_millis();
+            self.timeout_set.add(time + self.period, key);
+        }
+        true
+    }
+
     fn heartbeat_instance(&self, instance: &Instance) {
         if let Some(conn_manage) = &self.conn_manage {
             if let Some(addr) = conn_manage.upgrade() {
@@ -87,7 +195,7 @@ impl InnerNamingRegister {
         }
         if let Some(conn_manage) = &self.conn_manage {
             if let Some(addr) = conn_manage.upgrade() {
-                for instance in self.instances.values() {
+                for instance in self.instances.values().filter(|e| e.ephemeral) {
                     addr.do_send(NamingRequest::Register(instance.to_owned()));
                 }
             }
@@ -120,11 +228,19 @@ impl Actor for InnerNamingRegister {
     }
 }
 
+type RegisterResultSender = tokio::sync::oneshot::Sender<anyhow::Result<()>>;
+
 #[derive(Debug, Message)]
 #[rtype(result = "Result<(),std::io::Error>")]
 pub enum NamingRegisterCmd {
     Register(Instance),
     Remove(Instance),
+    /// 注册并返回服务端的处理结果;失败时实例仍会保留,由后台心跳或重连时继续注册
+    RegisterWithResult(Instance, RegisterResultSender),
+    RemoveWithResult(Instance, RegisterResultSender),
+    UpdateWithResult(Instance, RegisterResultSender),
+    /// 注销全部临时实例后停止
+    Shutdown(RegisterResultSender),
     Heartbeat(String, u64),
     Close,
     Reregister,
@@ -135,25 +251,32 @@ impl Handler<NamingRegisterCmd> for InnerNamingRegister {
 
     fn handle(&mut self, msg: NamingRegisterCmd, ctx: &mut Context<Self>) -> Self::Result {
         match msg {
-            NamingRegisterCmd::Register(mut instance) => {
-                instance.init_beat_string();
-                let key = instance.generate_key();
-                if self.instances.contains_key(&key) {
-                    return Ok(());
-                }
-                // request register
-                self.register_instance(instance.clone());
-                self.instances.insert(key.clone(), instance);
-                if !self.use_grpc {
-                    let time = now_millis();
-                    self.timeout_set.add(time + self.period, key);
+            NamingRegisterCmd::Register(instance) => {
+                if self.add_instance(instance.clone()) {
+                    // request register
+                    self.register_instance(instance);
                 }
             }
+            NamingRegisterCmd::RegisterWithResult(instance, sender) => {
+                self.add_instance(instance.clone());
+                self.request_with_result(NamingRequest::Register(instance), sender, ctx);
+            }
+            NamingRegisterCmd::UpdateWithResult(instance, sender) => {
+                self.update_instance(instance, sender, ctx);
+            }
+            NamingRegisterCmd::RemoveWithResult(instance, sender) => {
+                let key = instance.generate_key();
+                self.instances.remove(&key);
+                self.request_with_result(NamingRequest::Unregister(instance), sender, ctx);
+            }
             NamingRegisterCmd::Remove(instance) => {
                 let key = instance.generate_key();
                 if let Some(instance) = self.instances.remove(&key) {
                     // request unregister
                     self.remove_instance(instance, ctx);
+                } else if !instance.ephemeral {
+                    //持久化实例可能由其它进程注册
+                    self.remove_instance(instance, ctx);
                 }
             }
             NamingRegisterCmd::Heartbeat(key, time) => {
@@ -169,6 +292,9 @@ impl Handler<NamingRegisterCmd> for InnerNamingRegister {
             NamingRegisterCmd::Close => {
                 ctx.stop();
             }
+            NamingRegisterCmd::Shutdown(sender) => {
+                self.shutdown(sender, ctx);
+            }
             NamingRegisterCmd::Reregister => {
                 self.register_all_instances();
             }
@@ -1,16 +1,18 @@
 use crate::client;
-use crate::client::auth::{AuthActor, AuthCmd, AuthHandleResult};
+use crate::client::auth::{get_auth_headers, get_token_result, AuthActor};
+use crate::client::naming_client::api_model::BeatRequest;
 use crate::client::naming_client::Instance;
 use crate::client::naming_client::QueryInstanceListParams;
 use crate::client::naming_client::QueryListResult;
-use crate::client::utils::Utils;
-use crate::client::ServerEndpointInfo;
+use crate::client::utils::{ResponseWrap, Utils};
+use crate::client::{AuthResource, HostInfo, HttpClients, RequestOperation, ServerEndpointInfo};
+use crate::error::Error;
 use actix::Addr;
 use std::{collections::HashMap, sync::Arc};
 
 #[derive(Clone)]
 pub struct InnerNamingRequestClient {
-    pub(crate) client: reqwest::Client,
+    pub(crate) clients: HttpClients,
     pub(crate) headers: HashMap<String, String>,
     pub(crate) endpoints: Arc<ServerEndpointInfo>,
     pub(crate) auth_addr: Option<Addr<AuthActor>>,
@@ -39,10 +41,10 @@ impl InnerNamingRequestClient {
         endpoints: Arc<ServerEndpointInfo>,
         auth_addr: Option<Addr<AuthActor>>,
     ) -> Self {
-        let client = reqwest::Client::builder().build().unwrap();
+        let clients = HttpClients::new(&endpoints);
         Self {
             endpoints,
-            client,
+            clients,
             headers: client::Client::build_http_headers(),
             auth_addr,
         }
@@ -52,82 +54,143 @@ impl InnerNamingRequestClient {
         self.auth_addr = Some(addr);
     }
 
-    pub(crate) async fn get_token_result(&self) -> anyhow::Result<String> {
+    fn http_client(&self, host: &HostInfo) -> reqwest::Client {
+        self.clients.get(&self.endpoints, host)
+    }
+
+    /// 请求头与 url 中的 token 参数;token 按 `AccessTokenPosition` 放入请求头或 url
+    async fn build_auth_headers(
+        &self,
+        resource: AuthResource,
+    ) -> anyhow::Result<(String, HashMap<String, String>)> {
+        let mut headers = self.build_headers(resource).await;
+        //登录失败时返回 Unauthorized,不再发送未鉴权的请求
+        let token_param = match &self.auth_addr {
+            Some(auth_addr) => {
+                let token = get_token_result(auth_addr).await?;
+                self.endpoints
+                    .access_token_position
+                    .apply(&token, &mut headers)
+            }
+            None => String::new(),
+        };
+        Ok((token_param, headers))
+    }
+
+    /// 请求头,包含鉴权方式计算的头部
+    async fn build_headers(&self, resource: AuthResource) -> HashMap<String, String> {
+        let mut headers = self.headers.clone();
         if let Some(auth_addr) = &self.auth_addr {
-            match auth_addr.send(AuthCmd::QueryToken).await?? {
-                AuthHandleResult::None => {}
-                AuthHandleResult::Token(v) => {
-                    if v.len() > 0 {
-                        return Ok(format!("accessToken={}", &v));
-                    }
-                }
-            };
+            match get_auth_headers(auth_addr, resource).await {
+                Ok(auth_headers) => headers.extend(auth_headers),
+                Err(err) => log::warn!("get auth headers error,{}", err),
+            }
         }
-        Ok(String::new())
+        headers
     }
 
-    pub(crate) async fn get_token(&self) -> String {
-        self.get_token_result().await.unwrap_or_default()
+    pub(crate) async fn register(&self, instance: &Instance) -> anyhow::Result<()> {
+        self.do_instance_request(
+            "POST",
+            instance,
+            RequestOperation::RegisterInstance,
+            "register instance",
+        )
+        .await
     }
 
-    pub(crate) async fn register(&self, instance: &Instance) -> anyhow::Result<bool> {
-        let params = instance.to_web_params();
-        let body = serde_urlencoded::to_string(&params)?;
-        let host = self.endpoints.select_host();
-        let token_param = self.get_token().await;
-        let url = format!(
-            "http://{}:{}/nacos/v1/ns/instance?{}",
-            &host.ip, &host.port, token_param
-        );
-        let resp = Utils::request(
-            &self.client,
-            "POST",
-            &url,
-            body.as_bytes().to_vec(),
-            Some(&self.headers),
-            Some(3000),
+    pub(crate) async fn remove(&self, instance: &Instance) -> anyhow::Result<()> {
+        self.do_instance_request(
+            "DELETE",
+            instance,
+            RequestOperation::UnregisterInstance,
+            "remove instance",
         )
-        .await?;
-        //log::info!("register:{}",resp.get_lossy_string_body());
-        Ok("ok" == resp.get_string_body())
+        .await
     }
 
-    pub(crate) async fn remove(&self, instance: &Instance) -> anyhow::Result<bool> {
+    pub(crate) async fn update(&self, instance: &Instance) -> anyhow::Result<()> {
+        self.do_instance_request(
+            "PUT",
+            instance,
+            RequestOperation::RegisterInstance,
+            "update instance",
+        )
+        .await
+    }
+
+    async fn do_instance_request(
+        &self,
+        method: &str,
+        instance: &Instance,
+        operation: RequestOperation,
+        action: &str,
+    ) -> anyhow::Result<()> {
         let params = instance.to_web_params();
         let body = serde_urlencoded::to_string(&params)?;
         let host = self.endpoints.select_host();
-        let token_param = self.get_token().await;
+        let (token_param, headers) = self
+            .build_auth_headers(AuthResource::naming(
+                &instance.namespace_id,
+                &instance.group_name,
+                &instance.service_name,
+            ))
+            .await?;
         let url = format!(
-            "http://{}:{}/nacos/v1/ns/instance?{}",
-            &host.ip, &host.port, token_param
+            "{}/v1/ns/instance?{}",
+            self.endpoints.server_url(&host),
+            token_param
         );
         let resp = Utils::request(
-            &self.client,
-            "DELETE",
+            &self.http_client(&host),
+            method,
             &url,
             body.as_bytes().to_vec(),
-            Some(&self.headers),
-            Some(3000),
+            Some(&headers),
+            Some(self.endpoints.policy.timeout_millis(operation)),
         )
         .await?;
-        //log::info!("remove:{}",resp.get_lossy_string_body());
-        Ok("ok" == resp.get_string_body())
+        //log::info!("{}:{}",action,resp.get_lossy_string_body());
+        Self::check_ok_response(action, &resp)
+    }
+
+    fn check_ok_response(action: &str, resp: &ResponseWrap) -> anyhow::Result<()> {
+        let body = resp.get_lossy_string_body();
+        if "ok" == body {
+            Ok(())
+        } else {
+            let status = if resp.status_is_200() {
+                500
+            } else {
+                resp.status
+            };
+            Err(Error::from_http_status(status, format!("{} error,{}", action, body)).into())
+        }
     }
 
     pub(crate) async fn heartbeat(&self, beat_string: Arc<String>) -> anyhow::Result<bool> {
         let host = self.endpoints.select_host();
-        let token_param = self.get_token().await;
+        //心跳内容中的服务名已包含分组
+        let resource = serde_urlencoded::from_str::<BeatRequest>(&beat_string)
+            .map(|e| AuthResource::naming(&e.namespace_id, "", &e.service_name))
+            .unwrap_or_default();
+        let (token_param, headers) = self.build_auth_headers(resource).await?;
         let url = format!(
-            "http://{}:{}/nacos/v1/ns/instance/beat?{}",
-            &host.ip, &host.port, token_param
+            "{}/v1/ns/instance/beat?{}",
+            self.endpoints.server_url(&host),
+            token_param
         );
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "PUT",
             &url,
             beat_string.as_bytes().to_vec(),
-            Some(&self.headers),
-            Some(3000),
+            Some(&headers),
+            Some(
+                self.endpoints
+                    .policy
+                    .timeout_millis(RequestOperation::Heartbeat),
+            ),
         )
         .await?;
         //log::debug!("heartbeat:{}",resp.get_lossy_string_body());
@@ -139,22 +202,31 @@ impl InnerNamingRequestClient {
         query_param: &QueryInstanceListParams,
     ) -> anyhow::Result<QueryListResult> {
         let params = query_param.to_web_params();
-        let token_param = self.get_token().await;
+        let (token_param, headers) = self
+            .build_auth_headers(AuthResource::naming(
+                &query_param.namespace_id,
+                &query_param.group_name,
+                &query_param.service_name,
+            ))
+            .await?;
         let host = self.endpoints.select_host();
         let url = format!(
-            "http://{}:{}/nacos/v1/ns/instance/list?{}&{}",
-            &host.ip,
-            &host.port,
+            "{}/v1/ns/instance/list?{}&{}",
+            self.endpoints.server_url(&host),
             token_param,
             &serde_urlencoded::to_string(&params)?
         );
         let resp = Utils::request(
-            &self.client,
+            &self.http_client(&host),
             "GET",
             &url,
             vec![],
-            Some(&self.headers),
-            Some(3000),
+            Some(&headers),
+            Some(
+                self.endpoints
+                    .policy
+                    .timeout_millis(RequestOperation::QueryInstance),
+            ),
         )
         .await?;
 
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LabelRequirement {
+    Eq(String, String),
+    NotEq(String, String),
+    In(String, Vec<String>),
+    NotIn(String, Vec<String>),
+    Exists(String),
+    NotExists(String),
+}
+
+impl LabelRequirement {
+    fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        match self {
+            Self::Eq(k, v) => labels.get(k) == Some(v),
+            Self::NotEq(k, v) => labels.get(k) != Some(v),
+            Self::In(k, values) => labels.get(k).map(|v| values.contains(v)).unwrap_or(false),
+            Self::NotIn(k, values) => labels.get(k).map(|v| !values.contains(v)).unwrap_or(true),
+            Self::Exists(k) => labels.contains_key(k),
+            Self::NotExists(k) => !labels.contains_key(k),
+        }
+    }
+}
+
+/// 实例元数据标签选择器,多个条件用逗号分隔且需同时满足。
+/// 支持 `zone=cn-hz-a`、`env!=test`、`version in (v2,v3)`、`version notin (v1)`、`canary`、`!canary`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    requirements: Vec<LabelRequirement>,
+}
+
+impl LabelSelector {
+    pub fn parse(selector: &str) -> Result<Self> {
+        let mut requirements = vec![];
+        for item in Self::split(selector) {
+            let item = item.trim();
+            if item.is_empty() {
+                continue;
+            }
+            requirements.push(Self::parse_requirement(item)?);
+        }
+        Ok(Self { requirements })
+    }
+
+    /// 所有标签都需相等
+    pub fn from_labels(labels: &HashMap<String, String>) -> Self {
+        let requirements = labels
+            .iter()
+            .map(|(k, v)| LabelRequirement::Eq(k.to_owned(), v.to_owned()))
+            .collect();
+        Self { requirements }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.requirements.iter().all(|e| e.matches(labels))
+    }
+
+    //按括号外的逗号切分
+    fn split(selector: &str) -> Vec<&str> {
+        let mut list = vec![];
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in selector.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    list.push(&selector[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        list.push(&selector[start..]);
+        list
+    }
+
+    fn parse_requirement(item: &str) -> Result<LabelRequirement> {
+        if let Some((k, v)) = item.split_once("!=") {
+            return Ok(LabelRequirement::NotEq(
+                Self::parse_key(k, item)?,
+                v.trim().to_owned(),
+            ));
+        }
+        if let Some((k, v)) = item.split_once("==").or_else(|| item.split_once('=')) {
+            return Ok(LabelRequirement::Eq(
+                Self::parse_key(k, item)?,
+                v.trim().to_owned(),
+            ));
+        }
+        if let Some(pos) = item.find('(') {
+            let values = item[pos + 1..]
+                .strip_suffix(')')
+                .ok_or_else(|| Self::invalid(item))?;
+            let values = values
+                .split(',')
+                .map(|e| e.trim().to_owned())
+                .filter(|e| !e.is_empty())
+                .collect();
+            let mut words = item[..pos].split_whitespace();
+            let key = words.next().unwrap_or_default();
+            return match (words.next(), words.next()) {
+                (Some("in"), None) => Ok(LabelRequirement::In(Self::parse_key(key, item)?, values)),
+                (Some("notin"), None) => {
+                    Ok(LabelRequirement::NotIn(Self::parse_key(key, item)?, values))
+                }
+                _ => Err(Self::invalid(item)),
+            };
+        }
+        if let Some(k) = item.strip_prefix('!') {
+            return Ok(LabelRequirement::NotExists(Self::parse_key(k, item)?));
+        }
+        Ok(LabelRequirement::Exists(Self::parse_key(item, item)?))
+    }
+
+    fn parse_key(key: &str, item: &str) -> Result<String> {
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(Self::invalid(item));
+        }
+        Ok(key.to_owned())
+    }
+
+    fn invalid(item: &str) -> Error {
+        Error::Other(format!("invalid label selector:{}", item))
+    }
+}
+
+impl std::str::FromStr for LabelSelector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_selector() {
+        let mut labels = HashMap::new();
+        labels.insert("zone".to_owned(), "cn-hz-a".to_owned());
+        labels.insert("version".to_owned(), "v2".to_owned());
+        let matches = |s: &str| LabelSelector::parse(s).unwrap().matches(&labels);
+        assert!(matches(""));
+        assert!(matches("zone=cn-hz-a"));
+        assert!(matches("zone = cn-hz-a, version in (v2, v3)"));
+        assert!(matches("version notin (v1),env!=test,!canary,zone"));
+        assert!(!matches("zone=cn-hz-b"));
+        assert!(!matches("version in (v3)"));
+        assert!(!matches("canary"));
+        assert!(LabelSelector::parse("version on (v1)").is_err());
+        assert!(LabelSelector::parse("version in (v1").is_err());
+        assert!(LabelSelector::parse("=v1").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use super::{ConfigClient, NamingClient};
+
+/// 同一个名称下注册的客户端
+#[derive(Clone, Default)]
+pub struct NamedClients {
+    pub config_client: Option<Arc<ConfigClient>>,
+    pub naming_client: Option<Arc<NamingClient>>,
+}
+
+/// 按名称管理多个客户端,用于同时连接多个集群或命名空间
+#[derive(Default)]
+pub struct ClientRegistry {
+    clients: RwLock<HashMap<String, NamedClients>>,
+    last: RwLock<NamedClients>,
+}
+
+impl ClientRegistry {
+    pub fn register(
+        &self,
+        name: &str,
+        config_client: Option<Arc<ConfigClient>>,
+        naming_client: Option<Arc<NamingClient>>,
+    ) {
+        let mut clients = self.clients.write().unwrap();
+        clients.insert(
+            name.to_owned(),
+            NamedClients {
+                config_client,
+                naming_client,
+            },
+        );
+    }
+
+    pub fn register_config_client(&self, name: &str, client: Arc<ConfigClient>) {
+        let mut clients = self.clients.write().unwrap();
+        clients.entry(name.to_owned()).or_default().config_client = Some(client);
+    }
+
+    pub fn register_naming_client(&self, name: &str, client: Arc<NamingClient>) {
+        let mut clients = self.clients.write().unwrap();
+        clients.entry(name.to_owned()).or_default().naming_client = Some(client);
+    }
+
+    pub fn get(&self, name: &str) -> Option<NamedClients> {
+        self.clients.read().unwrap().get(name).cloned()
+    }
+
+    pub fn get_config_client(&self, name: &str) -> Option<Arc<ConfigClient>> {
+        self.get(name).and_then(|e| e.config_client)
+    }
+
+    pub fn get_naming_client(&self, name: &str) -> Option<Arc<NamingClient>> {
+        self.get(name).and_then(|e| e.naming_client)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.clients.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn remove(&self, name: &str) -> Option<NamedClients> {
+        self.clients.write().unwrap().remove(name)
+    }
+
+    /// 清空全部客户端,返回被移除的客户端;同一个客户端只返回一次
+    pub fn clear(&self) -> Vec<NamedClients> {
+        let mut all: Vec<NamedClients> = self
+            .clients
+            .write()
+            .unwrap()
+            .drain()
+            .map(|(_, v)| v)
+            .collect();
+        all.push(std::mem::take(&mut *self.last.write().unwrap()));
+        let mut list: Vec<NamedClients> = Vec::with_capacity(all.len());
+        for item in all {
+            let config_client = item.config_client.filter(|client| {
+                !list
+                    .iter()
+                    .filter_map(|e| e.config_client.as_ref())
+                    .any(|e| Arc::ptr_eq(e, client))
+            });
+            let naming_client = item.naming_client.filter(|client| {
+                !list
+                    .iter()
+                    .filter_map(|e| e.naming_client.as_ref())
+                    .any(|e| Arc::ptr_eq(e, client))
+            });
+            if config_client.is_some() || naming_client.is_some() {
+                list.push(NamedClients {
+                    config_client,
+                    naming_client,
+                });
+            }
+        }
+        list
+    }
+
+    pub fn get_last_config_client(&self) -> Option<Arc<ConfigClient>> {
+        self.last.read().unwrap().config_client.clone()
+    }
+
+    pub fn get_last_naming_client(&self) -> Option<Arc<NamingClient>> {
+        self.last.read().unwrap().naming_client.clone()
+    }
+
+    pub(crate) fn set_last_config_client(&self, client: Arc<ConfigClient>) {
+        self.last.write().unwrap().config_client = Some(client);
+    }
+
+    pub(crate) fn set_last_naming_client(&self, client: Arc<NamingClient>) {
+        self.last.write().unwrap().naming_client = Some(client);
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CLIENT_REGISTRY: ClientRegistry = ClientRegistry::default();
+}
+
+/// 全局客户端注册表
+pub fn registry() -> &'static ClientRegistry {
+    &CLIENT_REGISTRY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientBuilder;
+
+    #[test]
+    fn test_registry() {
+        let (config_client, naming_client) = ClientBuilder::new()
+            .set_endpoint_addrs("127.0.0.1:18848")
+            .set_use_grpc(false)
+            .build();
+        let registry = ClientRegistry::default();
+        registry.register(
+            "registry_a",
+            Some(config_client.clone()),
+            Some(naming_client.clone()),
+        );
+        registry.register_config_client("registry_b", config_client.clone());
+        assert!(Arc::ptr_eq(
+            &registry.get_config_client("registry_a").unwrap(),
+            &config_client
+        ));
+        assert!(registry.get_naming_client("registry_b").is_none());
+        let mut names = registry.names();
+        names.sort();
+        assert_eq!(names, vec!["registry_a", "registry_b"]);
+
+        assert!(registry.remove("registry_b").is_some());
+        assert!(registry.get("registry_b").is_none());
+
+        //同一个客户端在多个名称和最近创建的客户端中只返回一次
+        registry.register_config_client("registry_b", config_client.clone());
+        registry.set_last_config_client(config_client.clone());
+        registry.set_last_naming_client(naming_client.clone());
+        let list = registry.clear();
+        let config_clients: Vec<_> = list
+            .iter()
+            .filter_map(|e| e.config_client.as_ref())
+            .collect();
+        let naming_clients: Vec<_> = list
+            .iter()
+            .filter_map(|e| e.naming_client.as_ref())
+            .collect();
+        assert_eq!(config_clients.len(), 1);
+        assert!(Arc::ptr_eq(config_clients[0], &config_client));
+        assert_eq!(naming_clients.len(), 1);
+        assert!(Arc::ptr_eq(naming_clients[0], &naming_client));
+        assert!(registry.names().is_empty());
+        assert!(registry.get_last_config_client().is_none());
+    }
+
+    #[test]
+    fn test_global_registry() {
+        let config_client = ClientBuilder::new()
+            .set_endpoint_addrs("127.0.0.1:18848")
+            .set_use_grpc(false)
+            .set_registry_name(Some("test_global_registry".to_owned()))
+            .build_config_client();
+        assert!(Arc::ptr_eq(
+            &registry()
+                .get_config_client("test_global_registry")
+                .unwrap(),
+            &config_client
+        ));
+        assert!(registry().remove("test_global_registry").is_some());
+    }
+}
@@ -0,0 +1,153 @@
+use super::HostInfo;
+
+/// https 与 gRPC TLS 连接配置,需要开启 `tls` feature。
+///
+/// 未设置 ca 证书时使用系统根证书;设置 domain_name 时按该域名做 SNI 与证书校验。
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    /// PEM 格式的 ca 证书,可包含多个证书
+    pub ca_cert_pem: Option<Vec<u8>>,
+    /// mTLS 客户端证书,PEM 格式
+    pub client_cert_pem: Option<Vec<u8>>,
+    /// mTLS 客户端私钥,PEM 格式
+    pub client_key_pem: Option<Vec<u8>>,
+    /// 覆盖 SNI 与证书校验使用的域名
+    pub domain_name: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_ca_cert_pem(mut self, pem: Vec<u8>) -> Self {
+        self.ca_cert_pem = Some(pem);
+        self
+    }
+
+    pub fn set_client_identity_pem(mut self, cert_pem: Vec<u8>, key_pem: Vec<u8>) -> Self {
+        self.client_cert_pem = Some(cert_pem);
+        self.client_key_pem = Some(key_pem);
+        self
+    }
+
+    pub fn set_domain_name(mut self, domain_name: String) -> Self {
+        self.domain_name = Some(domain_name);
+        self
+    }
+
+    /// 请求地址中使用的主机名;设置 domain_name 时使用域名,由 http client 解析到实际地址
+    pub(crate) fn url_host<'a>(&'a self, host: &'a HostInfo) -> &'a str {
+        self.domain_name.as_deref().unwrap_or(&host.ip)
+    }
+
+    /// host 不为空时把 domain_name 解析到该地址(ip 或主机名),端口以请求地址为准;
+    /// 多个地址需分别创建 client,见 `HttpClients`
+    #[cfg(feature = "tls")]
+    pub(crate) fn apply_http(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+        host: Option<&HostInfo>,
+    ) -> anyhow::Result<reqwest::ClientBuilder> {
+        Self::install_crypto_provider();
+        if let Some(pem) = &self.ca_cert_pem {
+            for cert in reqwest::Certificate::from_pem_bundle(pem)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if let (Some(cert), Some(key)) = (&self.client_cert_pem, &self.client_key_pem) {
+            let mut pem = cert.clone();
+            pem.push(b'\n');
+            pem.extend_from_slice(key);
+            builder = builder.identity(reqwest::Identity::from_pem(&pem)?);
+        }
+        if let (Some(domain_name), Some(host)) = (&self.domain_name, host) {
+            builder = builder.dns_resolver(HostResolver {
+                domain_name: domain_name.to_ascii_lowercase(),
+                host: host.ip.trim_matches(|c| c == '[' || c == ']').to_owned(),
+            });
+        }
+        Ok(builder)
+    }
+
+    /// 使用 ring 作为 rustls 的默认加密实现;应用已设置时保留应用的设置
+    #[cfg(feature = "tls")]
+    pub(crate) fn install_crypto_provider() {
+        rustls::crypto::ring::default_provider()
+            .install_default()
+            .ok();
+    }
+
+    /// 检查证书与私钥能否被 http 与 gRPC 连接加载
+    #[cfg(feature = "tls")]
+    pub(crate) fn validate(&self) -> crate::error::Result<()> {
+        let check = || -> anyhow::Result<()> {
+            self.apply_http(reqwest::Client::builder(), None)?.build()?;
+            tonic::transport::Channel::from_static("https://127.0.0.1")
+                .tls_config(self.grpc_tls_config())?;
+            Ok(())
+        };
+        check().map_err(|err| crate::error::Error::Other(format!("invalid tls config,{:#}", err)))
+    }
+
+    #[cfg(feature = "tls")]
+    pub(crate) fn grpc_tls_config(&self) -> tonic::transport::ClientTlsConfig {
+        Self::install_crypto_provider();
+        use tonic::transport::{Certificate, Identity};
+        let mut config = tonic::transport::ClientTlsConfig::new().with_native_roots();
+        if let Some(pem) = &self.ca_cert_pem {
+            config = config.ca_certificate(Certificate::from_pem(pem));
+        }
+        if let (Some(cert), Some(key)) = (&self.client_cert_pem, &self.client_key_pem) {
+            config = config.identity(Identity::from_pem(cert, key));
+        }
+        if let Some(domain_name) = &self.domain_name {
+            config = config.domain_name(domain_name);
+        }
+        config
+    }
+}
+
+/// 把 domain_name 解析到所选的地址,地址为主机名时按主机名解析;其它域名正常解析
+#[cfg(feature = "tls")]
+struct HostResolver {
+    domain_name: String,
+    host: String,
+}
+
+#[cfg(feature = "tls")]
+impl reqwest::dns::Resolve for HostResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let target = if name.as_str().eq_ignore_ascii_case(&self.domain_name) {
+            self.host.clone()
+        } else {
+            name.as_str().to_owned()
+        };
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((target.as_str(), 0))
+                .await?
+                .collect();
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(TlsConfig::new()
+            .set_domain_name("nacos.example.com".to_owned())
+            .validate()
+            .is_ok());
+        let err = TlsConfig::new()
+            .set_client_identity_pem(b"bad cert".to_vec(), b"bad key".to_vec())
+            .validate()
+            .unwrap_err();
+        assert!(matches!(err, crate::error::Error::Other(_)), "{:?}", err);
+        let ca = b"-----BEGIN CERTIFICATE-----\nbad\n-----END CERTIFICATE-----\n".to_vec();
+        assert!(TlsConfig::new().set_ca_cert_pem(ca).validate().is_err());
+    }
+}
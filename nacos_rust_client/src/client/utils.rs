@@ -1,8 +1,13 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::Read;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
+//临时文件序号,同一进程内并发写同一文件时使用不同的临时文件
+static TMP_FILE_SEQ: AtomicU64 = AtomicU64::new(0);
+
 pub fn ms(millis: u64) -> Duration {
     Duration::from_millis(millis)
 }
@@ -119,4 +124,63 @@ impl Utils {
             Err(_) => None,
         }
     }
+
+    /// 先写临时文件再重命名,避免读到写了一半的内容
+    pub fn write_file_atomic(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+        tmp_name.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            TMP_FILE_SEQ.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp_path = path.with_file_name(tmp_name);
+        let result = (|| -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+            std::fs::rename(&tmp_path, path)
+        })();
+        if result.is_err() {
+            std::fs::remove_file(&tmp_path).ok();
+        }
+        Ok(result?)
+    }
+
+    /// 文件名只保留安全字符,其它字符按 %XX 编码
+    pub fn encode_path_segment(value: &str) -> String {
+        if value.is_empty() || value == "." || value == ".." {
+            return value.bytes().map(|b| format!("%{:02X}", b)).collect();
+        }
+        let mut result = String::with_capacity(value.len());
+        for b in value.bytes() {
+            if b.is_ascii_alphanumeric() || b == b'.' || b == b'-' || b == b'_' || b == b'@' {
+                result.push(b as char);
+            } else {
+                result.push_str(&format!("%{:02X}", b));
+            }
+        }
+        result
+    }
+
+    pub fn decode_path_segment(value: &str) -> String {
+        let bytes = value.as_bytes();
+        let mut result = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' && i + 2 < bytes.len() {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                if let Ok(b) = u8::from_str_radix(hex, 16) {
+                    result.push(b);
+                    i += 3;
+                    continue;
+                }
+            }
+            result.push(bytes[i]);
+            i += 1;
+        }
+        String::from_utf8_lossy(&result).into_owned()
+    }
 }
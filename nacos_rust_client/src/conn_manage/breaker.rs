@@ -3,19 +3,14 @@ use std::sync::Arc;
 
 use crate::client::now_millis;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum BreakerStatus {
+    #[default]
     Close,
     Open(u64),          //半开启时间戳
     HalfOpen(u32, u32), //(连续成功次数,已申请尝试次数)
 }
 
-impl Default for BreakerStatus {
-    fn default() -> Self {
-        Self::Close
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct BreakerConfig {
     ///失败多少次开启
@@ -2,44 +2,76 @@ use std::sync::Arc;
 
 use actix::prelude::*;
 
+use super::policy::RequestOperation;
 use crate::client::{
     config_client::ConfigKey,
     naming_client::{Instance, QueryInstanceListParams, ServiceInstanceKey},
 };
 
-#[derive(Debug, Message)]
+#[derive(Debug, Clone, Message)]
 #[rtype(result = "anyhow::Result<ConfigResponse>")]
 pub enum ConfigRequest {
     GetConfig(ConfigKey),
     SetConfig(ConfigKey, String),
+    CasSetConfig(ConfigKey, String, String), //(key,content,cas_md5)
     DeleteConfig(ConfigKey),
     V1Listen(String),                       // 兼容v1版本协议
     Listen(Vec<(ConfigKey, String)>, bool), //(key,md5)
 }
 
+impl ConfigRequest {
+    pub fn operation(&self) -> RequestOperation {
+        match self {
+            Self::GetConfig(_) => RequestOperation::GetConfig,
+            Self::SetConfig(_, _) | Self::CasSetConfig(_, _, _) => RequestOperation::PublishConfig,
+            Self::DeleteConfig(_) => RequestOperation::RemoveConfig,
+            Self::V1Listen(_) | Self::Listen(_, _) => RequestOperation::ListenConfig,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ConfigResponse {
     ConfigValue(String, String), // (content,md5)
     ChangeKeys(Vec<ConfigKey>),
+    CasConflict,
     None,
 }
 
-#[derive(Debug, Message)]
+#[derive(Debug, Clone, Message)]
 #[rtype(result = "anyhow::Result<NamingResponse>")]
 pub enum NamingRequest {
     Register(Instance),
     Unregister(Instance),
     BatchRegister(Vec<Instance>),
+    Update(Instance),
     Subscribe(Vec<ServiceInstanceKey>),
     Unsubscribe(Vec<ServiceInstanceKey>),
     QueryInstance(Box<QueryInstanceListParams>),
     V1Heartbeat(Arc<String>),
 }
 
+impl NamingRequest {
+    pub fn operation(&self) -> RequestOperation {
+        match self {
+            Self::Register(_) | Self::BatchRegister(_) | Self::Update(_) => {
+                RequestOperation::RegisterInstance
+            }
+            Self::Unregister(_) => RequestOperation::UnregisterInstance,
+            Self::Subscribe(_) | Self::Unsubscribe(_) => RequestOperation::Subscribe,
+            Self::QueryInstance(_) => RequestOperation::QueryInstance,
+            Self::V1Heartbeat(_) => RequestOperation::Heartbeat,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ServiceResult {
     pub hosts: Vec<Arc<Instance>>,
     pub cache_millis: Option<u64>,
+    pub protect_threshold: Option<f32>,
+    /// 服务端返回的健康实例占比已低于保护阈值
+    pub reach_protection_threshold: bool,
 }
 
 #[derive(Debug)]
@@ -52,5 +84,6 @@ pub enum NamingResponse {
 #[rtype(result = "anyhow::Result<()>")]
 pub enum ConnCallbackMsg {
     ConfigChange(ConfigKey, String, String),
+    ConfigDelete(ConfigKey),
     InstanceChange(ServiceInstanceKey, ServiceResult),
 }
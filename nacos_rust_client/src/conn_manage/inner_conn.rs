@@ -11,7 +11,7 @@ use crate::client::auth::AuthActor;
 use crate::{
     client::{
         config_client::inner_client::ConfigInnerRequestClient,
-        naming_client::InnerNamingRequestClient, ClientInfo, HostInfo,
+        naming_client::InnerNamingRequestClient, ClientInfo, HostInfo, ServerEndpointInfo,
     },
     grpc::grpc_client::InnerGrpcClient,
 };
@@ -21,6 +21,8 @@ pub(crate) struct InnerConn {
     pub id: u32,
     pub weight: u64,
     pub host_info: HostInfo,
+    //只包含当前地址的连接配置
+    pub(crate) endpoint: Arc<ServerEndpointInfo>,
     pub breaker: Breaker,
     pub support_grpc: bool,
     pub channel: Option<Channel>,
@@ -44,6 +46,10 @@ impl InnerConn {
         Self {
             id,
             weight: 1,
+            endpoint: Arc::new(ServerEndpointInfo {
+                hosts: vec![host_info.clone()],
+                ..Default::default()
+            }),
             host_info,
             support_grpc,
             breaker: Breaker::new(Default::default(), breaker_config),
@@ -59,18 +65,22 @@ impl InnerConn {
 
     pub fn init_grpc(&mut self, manage_addr: WeakAddr<ConnManage>) -> anyhow::Result<()> {
         if self.support_grpc {
-            let addr = format!(
-                "http://{}:{}",
-                &self.host_info.ip, &self.host_info.grpc_port
-            );
-            let channel = Channel::from_shared(addr)?.connect_lazy();
+            let addr = self.endpoint.grpc_url(&self.host_info);
+            #[allow(unused_mut)]
+            let mut endpoint = Channel::from_shared(addr)?;
+            #[cfg(feature = "tls")]
+            if let Some(tls) = &self.endpoint.tls {
+                endpoint = endpoint.tls_config(tls.grpc_tls_config())?;
+            }
+            let channel = endpoint.connect_lazy();
             let grpc_client = InnerGrpcClient::new_by_channel(
                 self.id.to_owned(),
                 channel.clone(),
                 manage_addr,
                 self.client_info.clone(),
                 self.auth_addr.clone(),
-            )?;
+            )?
+            .set_policy(self.endpoint.policy.clone());
             self.channel = Some(channel);
             self.grpc_client_addr = Some(grpc_client.start());
         }
@@ -4,9 +4,11 @@ use actix::{prelude::*, WeakAddr};
 
 use crate::{
     client::{
-        auth::AuthActor,
+        auth::{AuthActor, AuthCmd},
         config_client::{
-            inner::ConfigInnerCmd, inner_client::ConfigInnerRequestClient, model::NotifyConfigItem,
+            inner::ConfigInnerCmd,
+            inner_client::ConfigInnerRequestClient,
+            model::{ConfigPublishResult, NotifyConfigItem},
             ConfigInnerActor,
         },
         get_md5,
@@ -17,7 +19,8 @@ use crate::{
         },
         AuthInfo, ClientInfo, HostInfo, ServerEndpointInfo,
     },
-    grpc::grpc_client::InnerGrpcClient,
+    error::Error,
+    grpc::grpc_client::{InnerGrpcClient, InnerGrpcClientCmd},
     init_global_system_actor, ActorCreate,
 };
 
@@ -28,6 +31,7 @@ use super::{
         ServiceResult,
     },
     inner_conn::InnerConn,
+    policy::RequestOperation,
     NotifyCallbackAddr,
 };
 
@@ -38,12 +42,17 @@ pub struct ConnManage {
     current_index: usize,
     support_grpc: bool,
     auth_info: Option<AuthInfo>,
-    _conn_globda_id: u32,
-    _breaker_config: Arc<BreakerConfig>,
+    conn_global_id: u32,
+    breaker_config: Arc<BreakerConfig>,
     pub(crate) callback: NotifyCallbackAddr,
     reconnecting: bool,
+    //连续重连次数,用于计算重连等待时间
+    reconnect_times: u32,
     client_info: Arc<ClientInfo>,
-    //auth_addr: Addr<AuthActor>,
+    auth_addr: Addr<AuthActor>,
+    //新增地址时使用的连接配置
+    endpoint: ServerEndpointInfo,
+    address_client: Option<reqwest::Client>,
 }
 
 impl ConnManage {
@@ -55,7 +64,10 @@ impl ConnManage {
         client_info: Arc<ClientInfo>,
         auth_addr: Addr<AuthActor>,
     ) -> Self {
-        assert!(!hosts.is_empty());
+        let endpoint = ServerEndpointInfo {
+            hosts: hosts.clone(),
+            ..Default::default()
+        };
         let mut id = 0;
         let breaker_config = Arc::new(breaker_config);
         let mut conns = Vec::with_capacity(hosts.len());
@@ -79,12 +91,15 @@ impl ConnManage {
             current_index: 0,
             support_grpc,
             auth_info,
-            _conn_globda_id: id,
-            _breaker_config: breaker_config,
+            conn_global_id: id,
+            breaker_config,
             callback: Default::default(),
             reconnecting: false,
+            reconnect_times: 0,
             client_info,
-            //auth_addr,
+            auth_addr,
+            endpoint,
+            address_client: None,
         }
     }
 
@@ -93,9 +108,114 @@ impl ConnManage {
         self
     }
 
+    /// 使用 endpoint 中的连接配置(如 TLS)连接各个服务地址;
+    /// 配置了地址服务器时定时拉取地址列表,增减连接
+    pub fn set_endpoint(mut self, endpoint: &ServerEndpointInfo) -> Self {
+        for conn in self.conns.iter_mut() {
+            conn.endpoint = Arc::new(endpoint.for_host(conn.host_info.clone()));
+        }
+        if endpoint.address_server.is_some() {
+            self.address_client = Some(endpoint.build_http_client());
+        }
+        self.endpoint = endpoint.clone();
+        self
+    }
+
+    fn new_conn(&mut self, host: HostInfo) -> InnerConn {
+        let mut conn = InnerConn::new(
+            self.conn_global_id,
+            host,
+            self.support_grpc,
+            self.breaker_config.clone(),
+            self.client_info.clone(),
+            self.auth_addr.clone(),
+        );
+        conn.endpoint = Arc::new(self.endpoint.for_host(conn.host_info.clone()));
+        self.conn_global_id += 1;
+        conn
+    }
+
+    fn index_of(&self, id: u32) -> Option<usize> {
+        self.conns.iter().position(|e| e.id == id)
+    }
+
+    fn current_conn_id(&self) -> Option<u32> {
+        self.conns.get(self.current_index).map(|e| e.id)
+    }
+
+    fn refresh_server_list(&mut self, ctx: &mut Context<Self>, wait: bool) {
+        let (address_server, client) = match (&self.endpoint.address_server, &self.address_client) {
+            (Some(address_server), Some(client)) => (address_server.clone(), client.clone()),
+            _ => return,
+        };
+        let fut = async move { address_server.fetch(&client).await }
+            .into_actor(self)
+            .map(|result, act, ctx| match result {
+                Ok(hosts) => act.update_conns(hosts, ctx),
+                Err(err) => log::warn!("fetch server list from address server error,{}", err),
+            });
+        if wait {
+            fut.wait(ctx);
+        } else {
+            fut.spawn(ctx);
+        }
+    }
+
+    /// 按地址服务器返回的地址列表增减连接;当前连接的地址下线时切换到其它地址
+    fn update_conns(&mut self, hosts: Vec<HostInfo>, ctx: &mut Context<Self>) {
+        fn is_same(a: &HostInfo, b: &HostInfo) -> bool {
+            a.ip == b.ip && a.port == b.port && a.grpc_port == b.grpc_port
+        }
+        let current_id = self.current_conn_id();
+        let mut old_conns = std::mem::take(&mut self.conns);
+        let mut conns: Vec<InnerConn> = Vec::with_capacity(hosts.len());
+        for host in hosts {
+            if conns.iter().any(|e| is_same(&e.host_info, &host)) {
+                continue;
+            }
+            match old_conns.iter().position(|e| is_same(&e.host_info, &host)) {
+                Some(index) => conns.push(old_conns.swap_remove(index)),
+                None => {
+                    log::info!("ConnManage add server {}:{}", &host.ip, &host.port);
+                    conns.push(self.new_conn(host));
+                }
+            }
+        }
+        for mut conn in old_conns {
+            log::info!(
+                "ConnManage remove server {}:{}",
+                &conn.host_info.ip,
+                &conn.host_info.port
+            );
+            if let Some(addr) = conn.grpc_client_addr.take() {
+                addr.do_send(InnerGrpcClientCmd::Close);
+            }
+            conn.close_grpc().ok();
+        }
+        self.conns = conns;
+        match current_id.and_then(|id| self.index_of(id)) {
+            Some(index) => self.current_index = index,
+            None => {
+                self.init_conn(ctx);
+                self.reconnect_notify(ctx);
+            }
+        }
+    }
+
     fn init_conn(&mut self, ctx: &mut Context<Self>) {
+        if self.conns.is_empty() {
+            log::warn!("ConnManage init connect,server list is empty");
+            return;
+        }
         self.current_index = self.select_index();
-        let conn = self.conns.get_mut(self.current_index).unwrap();
+        self.connect_current(ctx);
+    }
+
+    fn connect_current(&mut self, ctx: &mut Context<Self>) {
+        let conn = match self.conns.get_mut(self.current_index) {
+            Some(conn) => conn,
+            None => return,
+        };
         log::info!(
             "ConnManage init connect,host {}:{}",
             &conn.host_info.ip,
@@ -110,10 +230,54 @@ impl ConnManage {
         }
     }
 
-    fn init_http_request(conn: &mut InnerConn, auth_info: &Option<AuthInfo>) {
-        let endpoints = Arc::new(ServerEndpointInfo {
-            hosts: vec![conn.host_info.clone()],
+    /// 服务端要求重置连接;指定了服务地址时切换到该地址,否则切换到其它地址
+    fn connect_reset(&mut self, id: u32, server: Option<(String, u32)>, ctx: &mut Context<Self>) {
+        if !self.support_grpc || self.current_conn_id() != Some(id) {
+            return;
+        }
+        let target_index = server.map(|(ip, port)| {
+            match self
+                .conns
+                .iter()
+                .position(|e| e.host_info.ip == ip && e.host_info.port == port)
+            {
+                Some(index) => index,
+                None => {
+                    let conn = self.new_conn(HostInfo::new(&ip, port));
+                    self.conns.push(conn);
+                    self.conns.len() - 1
+                }
+            }
         });
+        let old_index = self.current_index;
+        if let Some(conn) = self.conns.get_mut(old_index) {
+            log::info!(
+                "ConnManage connect reset,host {}:{}",
+                &conn.host_info.ip,
+                &conn.host_info.port
+            );
+            if let Some(addr) = conn.grpc_client_addr.take() {
+                addr.do_send(InnerGrpcClientCmd::Close);
+            }
+            conn.close_grpc().ok();
+        }
+        match target_index {
+            Some(index) => {
+                self.current_index = index;
+                self.connect_current(ctx);
+            }
+            None if self.conns.len() > 1 => {
+                self.conns[old_index].weight = 0;
+                self.init_conn(ctx);
+                self.conns[old_index].weight = 1;
+            }
+            None => self.init_conn(ctx),
+        }
+        self.reconnect_notify(ctx);
+    }
+
+    fn init_http_request(conn: &mut InnerConn, auth_info: &Option<AuthInfo>) {
+        let endpoints = conn.endpoint.clone();
         let auth_actor = AuthActor::new(endpoints.clone(), auth_info.clone());
         let auth_actor_addr = auth_actor.start();
         //config http
@@ -142,6 +306,20 @@ impl ConnManage {
         }
     }
 
+    fn close_if_unused(&mut self, ctx: &mut Context<Self>) {
+        if self.callback.is_active() {
+            return;
+        }
+        log::info!("ConnManage close");
+        for conn in self.conns.iter_mut() {
+            if let Some(addr) = conn.grpc_client_addr.take() {
+                addr.do_send(InnerGrpcClientCmd::Close);
+            }
+            conn.close_grpc().ok();
+        }
+        ctx.stop();
+    }
+
     fn select_index(&self) -> usize {
         NamingUtils::select_by_weight_fn(&self.conns, |e| {
             if e.breaker.is_close() {
@@ -154,24 +332,32 @@ impl ConnManage {
         })
     }
 
-    fn reconnect(&mut self, old_index: usize, ctx: &mut Context<Self>) {
-        if self.reconnecting || self.current_index != old_index {
+    fn reconnect(&mut self, old_id: u32, ctx: &mut Context<Self>) {
+        if self.reconnecting || self.current_conn_id() != Some(old_id) {
             //log::debug!("ConnManage reconnect,ignore repeated");
             //已经重链过
             return;
         }
         self.reconnecting = true;
-        ctx.run_later(Duration::from_millis(1000), move |act, ctx| {
+        let delay = self
+            .endpoint
+            .policy
+            .reconnect_backoff
+            .delay(self.reconnect_times);
+        self.reconnect_times = self.reconnect_times.saturating_add(1);
+        ctx.run_later(delay, move |act, ctx| {
             log::info!("ConnManage reconnect");
             if act.conns.len() == 1 {
                 act.init_conn(ctx);
             } else {
-                if let Some(conn) = act.conns.get_mut(old_index) {
+                //地址列表可能已更新,按 id 查找
+                let old_index = act.index_of(old_id);
+                if let Some(conn) = old_index.and_then(|i| act.conns.get_mut(i)) {
                     conn.close_grpc().ok();
                     conn.weight = 0;
                 }
                 act.init_conn(ctx);
-                if let Some(conn) = act.conns.get_mut(old_index) {
+                if let Some(conn) = old_index.and_then(|i| act.conns.get_mut(i)) {
                     conn.weight = 1;
                 }
             }
@@ -201,15 +387,12 @@ impl ConnManage {
         }
     }
 
-    fn check_reconnect(
-        &mut self,
-        current_index: usize,
-        request_is_ok: bool,
-        ctx: &mut Context<Self>,
-    ) {
-        let can_try = if let Some(conn) = self.conns.get_mut(current_index) {
+    fn check_reconnect(&mut self, conn_id: u32, request_is_ok: bool, ctx: &mut Context<Self>) {
+        let conn = self.index_of(conn_id).and_then(|i| self.conns.get_mut(i));
+        let can_try = if let Some(conn) = conn {
             if request_is_ok {
                 conn.breaker.success();
+                self.reconnect_times = 0;
                 true
             } else {
                 conn.breaker.error();
@@ -219,8 +402,160 @@ impl ConnManage {
             true
         };
         if !can_try {
-            self.reconnect(current_index, ctx);
+            self.reconnect(conn_id, ctx);
+        }
+    }
+
+    /// 幂等请求网络错误且未超过重试次数时返回重试前的等待时间;重试时使用重连后的当前连接
+    fn retry_delay(
+        &mut self,
+        operation: RequestOperation,
+        request_is_ok: bool,
+        retry_times: u32,
+    ) -> Option<Duration> {
+        if request_is_ok
+            || !operation.is_idempotent()
+            || retry_times >= self.endpoint.policy.max_retries
+        {
+            return None;
         }
+        Some(self.endpoint.policy.retry_backoff.delay(retry_times))
+    }
+
+    /// 服务端返回 403 或 gRPC 鉴权错误时通知鉴权 actor 重新登录;返回是否为鉴权错误
+    fn check_unauthorized(&self, conn_id: u32, err: Option<&anyhow::Error>) -> bool {
+        if !err.map(Error::anyhow_is_unauthorized).unwrap_or(false) {
+            return false;
+        }
+        self.auth_addr.do_send(AuthCmd::Invalidate);
+        if let Some(conn) = self.index_of(conn_id).and_then(|i| self.conns.get(i)) {
+            let http_auth_addrs = [
+                conn.config_request_client
+                    .as_ref()
+                    .and_then(|e| e.auth_addr.as_ref()),
+                conn.naming_request_client
+                    .as_ref()
+                    .and_then(|e| e.auth_addr.as_ref()),
+            ];
+            for auth_addr in http_auth_addrs.into_iter().flatten() {
+                if auth_addr != &self.auth_addr {
+                    auth_addr.do_send(AuthCmd::Invalidate);
+                }
+            }
+        }
+        true
+    }
+
+    /// 鉴权错误重新登录后立即重试一次,不受 max_retries 限制
+    fn unauthorized_retry_delay(unauthorized: bool, retry_times: u32) -> Option<Duration> {
+        if unauthorized && retry_times == 0 {
+            Some(Duration::ZERO)
+        } else {
+            None
+        }
+    }
+
+    fn do_config_request_with_retry(
+        &mut self,
+        msg: ConfigRequest,
+        retry_times: u32,
+        _ctx: &mut Context<Self>,
+    ) -> ResponseActFuture<Self, anyhow::Result<ConfigResponse>> {
+        let conn = match self.conns.get(self.current_index) {
+            Some(conn) => conn,
+            None => return Box::pin(fut::ready(Err(Self::empty_server_error()))),
+        };
+        let conn_addr = conn.grpc_client_addr.clone();
+        let conn_id = conn.id;
+        let support_grpc = self.support_grpc;
+        let config_client = conn.config_request_client.clone();
+        let retry_msg = msg.clone();
+        let operation = msg.operation();
+        let fut = async move {
+            let r = Self::do_config_request(msg, support_grpc, conn_addr, config_client).await;
+            (r, conn_id)
+        }
+        .into_actor(self)
+        .then(move |(r, conn_id), act, ctx| {
+            // 服务端返回的业务错误(如配置不存在)不计入熔断
+            let request_is_ok = r
+                .as_ref()
+                .map_or_else(Error::anyhow_is_server_reply, |_| true);
+            act.check_reconnect(conn_id, request_is_ok, ctx);
+            let unauthorized = act.check_unauthorized(conn_id, r.as_ref().err());
+            match act
+                .retry_delay(operation, request_is_ok, retry_times)
+                .or_else(|| Self::unauthorized_retry_delay(unauthorized, retry_times))
+            {
+                Some(delay) => {
+                    log::warn!(
+                        "config request error,retry after {:?},{}",
+                        delay,
+                        r.unwrap_err()
+                    );
+                    Box::pin(fut::wrap_future(tokio::time::sleep(delay)).then(
+                        move |_, act: &mut Self, ctx| {
+                            act.do_config_request_with_retry(retry_msg, retry_times + 1, ctx)
+                        },
+                    )) as ResponseActFuture<Self, _>
+                }
+                None => Box::pin(fut::ready(r)),
+            }
+        });
+        Box::pin(fut)
+    }
+
+    fn do_naming_request_with_retry(
+        &mut self,
+        msg: NamingRequest,
+        retry_times: u32,
+        _ctx: &mut Context<Self>,
+    ) -> ResponseActFuture<Self, anyhow::Result<NamingResponse>> {
+        let conn = match self.conns.get(self.current_index) {
+            Some(conn) => conn,
+            None => return Box::pin(fut::ready(Err(Self::empty_server_error()))),
+        };
+        let conn_addr = conn.grpc_client_addr.clone();
+        let support_grpc = self.support_grpc;
+        let naming_client = conn.naming_request_client.clone();
+        let conn_id = conn.id;
+        let retry_msg = msg.clone();
+        let operation = msg.operation();
+        let fut = async move {
+            let r = Self::do_naming_request(msg, support_grpc, conn_addr, naming_client).await;
+            (r, conn_id)
+        }
+        .into_actor(self)
+        .then(move |(r, conn_id), act, ctx| {
+            let request_is_ok = r
+                .as_ref()
+                .map_or_else(Error::anyhow_is_server_reply, |_| true);
+            act.check_reconnect(conn_id, request_is_ok, ctx);
+            let unauthorized = act.check_unauthorized(conn_id, r.as_ref().err());
+            match act
+                .retry_delay(operation, request_is_ok, retry_times)
+                .or_else(|| Self::unauthorized_retry_delay(unauthorized, retry_times))
+            {
+                Some(delay) => {
+                    log::warn!(
+                        "naming request error,retry after {:?},{}",
+                        delay,
+                        r.unwrap_err()
+                    );
+                    Box::pin(fut::wrap_future(tokio::time::sleep(delay)).then(
+                        move |_, act: &mut Self, ctx| {
+                            act.do_naming_request_with_retry(retry_msg, retry_times + 1, ctx)
+                        },
+                    )) as ResponseActFuture<Self, _>
+                }
+                None => Box::pin(fut::ready(r)),
+            }
+        });
+        Box::pin(fut)
+    }
+
+    fn empty_server_error() -> anyhow::Error {
+        Error::Transport("server list is empty".to_owned()).into()
     }
 
     async fn do_config_request(
@@ -231,13 +566,10 @@ impl ConnManage {
     ) -> anyhow::Result<ConfigResponse> {
         if support_grpc {
             if let Some(conn_addr) = conn_addr {
-                if let Ok(Ok(res)) = conn_addr.send(msg).await {
-                    Ok(res)
-                } else {
-                    Err(anyhow::anyhow!("grpc request error"))
-                }
+                let res: ConfigResponse = conn_addr.send(msg).await??;
+                Ok(res)
             } else {
-                Err(anyhow::anyhow!("grpc conn is empty"))
+                Err(Error::Transport("grpc conn is empty".to_owned()).into())
             }
         } else if let Some(config_client) = config_client {
             match msg {
@@ -250,6 +582,15 @@ impl ConnManage {
                     config_client.set_config(&config_key, &value).await?;
                     Ok(ConfigResponse::None)
                 }
+                ConfigRequest::CasSetConfig(config_key, value, cas_md5) => {
+                    match config_client
+                        .set_config_cas(&config_key, &value, &cas_md5)
+                        .await?
+                    {
+                        ConfigPublishResult::Published => Ok(ConfigResponse::None),
+                        ConfigPublishResult::Conflict => Ok(ConfigResponse::CasConflict),
+                    }
+                }
                 ConfigRequest::DeleteConfig(config_key) => {
                     config_client.del_config(&config_key).await?;
                     Ok(ConfigResponse::None)
@@ -276,7 +617,7 @@ impl ConnManage {
                 let res: NamingResponse = conn_addr.send(msg).await??;
                 Ok(res)
             } else {
-                Err(anyhow::anyhow!("grpc conn is empty"))
+                Err(Error::Transport("grpc conn is empty".to_owned()).into())
             }
         } else if let Some(naming_client) = naming_client {
             match msg {
@@ -288,6 +629,10 @@ impl ConnManage {
                     naming_client.remove(&instance).await?;
                     Ok(NamingResponse::None)
                 }
+                NamingRequest::Update(instance) => {
+                    naming_client.update(&instance).await?;
+                    Ok(NamingResponse::None)
+                }
                 NamingRequest::BatchRegister(_) => Err(anyhow::anyhow!("http not support")),
                 NamingRequest::Subscribe(_) => Err(anyhow::anyhow!("http not support")),
                 NamingRequest::Unsubscribe(_) => Err(anyhow::anyhow!("http not support")),
@@ -302,6 +647,8 @@ impl ConnManage {
                     let service_result = ServiceResult {
                         hosts,
                         cache_millis: result.cache_millis,
+                        protect_threshold: result.protect_threshold,
+                        ..Default::default()
                     };
                     Ok(NamingResponse::ServiceResult(service_result))
                 }
@@ -325,6 +672,14 @@ impl Actor for ConnManage {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         log::info!("ConnManage started");
+        if let Some(address_server) = &self.endpoint.address_server {
+            let refresh_interval = address_server.refresh_interval();
+            //首次拉取完成前不处理请求
+            self.refresh_server_list(ctx, true);
+            ctx.run_interval(refresh_interval, |act, ctx| {
+                act.refresh_server_list(ctx, false);
+            });
+        }
         self.init_conn(ctx);
     }
 }
@@ -335,7 +690,18 @@ pub enum ConnManageCmd {
     ConfigInnerActorAddr(WeakAddr<ConfigInnerActor>),
     NamingListenerActorAddr(WeakAddr<InnerNamingListener>),
     NamingRegisterActorAddr(WeakAddr<InnerNamingRegister>),
-    GrpcRequestCheckError { id: u32 },
+    GrpcRequestCheckError {
+        id: u32,
+    },
+    /// 服务端发送 ConnectResetRequest;server 为服务端建议切换的地址(ip,port)
+    ConnectReset {
+        id: u32,
+        server: Option<(String, u32)>,
+    },
+    /// 配置客户端关闭后不再使用连接;没有其它客户端在使用时关闭连接
+    DetachConfigClient,
+    /// 服务客户端关闭后不再使用连接;没有其它客户端在使用时关闭连接
+    DetachNamingClient,
 }
 
 impl Handler<ConnManageCmd> for ConnManage {
@@ -352,7 +718,17 @@ impl Handler<ConnManageCmd> for ConnManage {
             ConnManageCmd::NamingRegisterActorAddr(addr) => {
                 self.callback.naming_register_addr = Some(addr);
             }
-            ConnManageCmd::GrpcRequestCheckError { id } => self.reconnect(id as usize, ctx),
+            ConnManageCmd::GrpcRequestCheckError { id } => self.reconnect(id, ctx),
+            ConnManageCmd::ConnectReset { id, server } => self.connect_reset(id, server, ctx),
+            ConnManageCmd::DetachConfigClient => {
+                self.callback.config_inner_addr = None;
+                self.close_if_unused(ctx);
+            }
+            ConnManageCmd::DetachNamingClient => {
+                self.callback.naming_listener_addr = None;
+                self.callback.naming_register_addr = None;
+                self.close_if_unused(ctx);
+            }
         }
         Ok(())
     }
@@ -375,6 +751,13 @@ impl Handler<ConnCallbackMsg> for ConnManage {
                         }
                     }
                 }
+                ConnCallbackMsg::ConfigDelete(config_key) => {
+                    if let Some(config_addr) = callback.config_inner_addr {
+                        if let Some(config_addr) = config_addr.upgrade() {
+                            config_addr.do_send(ConfigInnerCmd::NotifyDelete(config_key));
+                        }
+                    }
+                }
                 ConnCallbackMsg::InstanceChange(key, service_result) => {
                     if let Some(config_addr) = callback.naming_listener_addr {
                         if let Some(config_addr) = config_addr.upgrade() {
@@ -395,43 +778,15 @@ impl Handler<ConnCallbackMsg> for ConnManage {
 impl Handler<ConfigRequest> for ConnManage {
     type Result = ResponseActFuture<Self, anyhow::Result<ConfigResponse>>;
 
-    fn handle(&mut self, msg: ConfigRequest, _ctx: &mut Self::Context) -> Self::Result {
-        let conn = self.conns.get(self.current_index).unwrap();
-        let conn_addr = conn.grpc_client_addr.clone();
-        let current_index = self.current_index.to_owned();
-        let support_grpc = self.support_grpc;
-        let config_client = conn.config_request_client.clone();
-        let fut = async move {
-            let r = Self::do_config_request(msg, support_grpc, conn_addr, config_client).await;
-            (r, current_index)
-        }
-        .into_actor(self)
-        .map(|(r, current_index), act, ctx| {
-            act.check_reconnect(current_index, r.is_ok(), ctx);
-            r
-        });
-        Box::pin(fut)
+    fn handle(&mut self, msg: ConfigRequest, ctx: &mut Self::Context) -> Self::Result {
+        self.do_config_request_with_retry(msg, 0, ctx)
     }
 }
 
 impl Handler<NamingRequest> for ConnManage {
     type Result = ResponseActFuture<Self, anyhow::Result<NamingResponse>>;
 
-    fn handle(&mut self, msg: NamingRequest, _ctx: &mut Self::Context) -> Self::Result {
-        let conn = self.conns.get(self.current_index).unwrap();
-        let conn_addr = conn.grpc_client_addr.clone();
-        let support_grpc = self.support_grpc;
-        let naming_client = conn.naming_request_client.clone();
-        let current_index = self.current_index.to_owned();
-        let fut = async move {
-            let r = Self::do_naming_request(msg, support_grpc, conn_addr, naming_client).await;
-            (r, current_index)
-        }
-        .into_actor(self)
-        .map(|(r, current_index), act, ctx| {
-            act.check_reconnect(current_index, r.is_ok(), ctx);
-            r
-        });
-        Box::pin(fut)
+    fn handle(&mut self, msg: NamingRequest, ctx: &mut Self::Context) -> Self::Result {
+        self.do_naming_request_with_retry(msg, 0, ctx)
     }
 }
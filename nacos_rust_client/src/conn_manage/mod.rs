@@ -1,4 +1,4 @@
-use actix::WeakAddr;
+use actix::{Actor, WeakAddr};
 
 use crate::client::{
     config_client::inner::ConfigInnerActor,
@@ -10,6 +10,7 @@ pub mod conn_msg;
 pub mod endpoint;
 pub(crate) mod inner_conn;
 pub mod manage;
+pub mod policy;
 
 #[derive(Default, Clone)]
 pub struct NotifyCallbackAddr {
@@ -17,3 +18,19 @@ pub struct NotifyCallbackAddr {
     pub(crate) naming_listener_addr: Option<WeakAddr<InnerNamingListener>>,
     pub(crate) naming_register_addr: Option<WeakAddr<InnerNamingRegister>>,
 }
+
+impl NotifyCallbackAddr {
+    /// 是否还有客户端在使用连接
+    pub(crate) fn is_active(&self) -> bool {
+        Self::is_connected(&self.config_inner_addr)
+            || Self::is_connected(&self.naming_listener_addr)
+            || Self::is_connected(&self.naming_register_addr)
+    }
+
+    fn is_connected<T: Actor>(addr: &Option<WeakAddr<T>>) -> bool {
+        addr.as_ref()
+            .and_then(WeakAddr::upgrade)
+            .map(|e| e.connected())
+            .unwrap_or(false)
+    }
+}
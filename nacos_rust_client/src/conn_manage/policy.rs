@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::breaker::BreakerConfig;
+
+/// 指数退避:第 n 次等待 min(base * 2^n, max),再按 jitter 比例随机增减
+#[derive(Debug, Clone)]
+pub struct BackoffConfig {
+    base: Duration,
+    max: Duration,
+    /// 随机抖动比例,取值 0~1;0 表示不抖动
+    jitter: f64,
+}
+
+impl BackoffConfig {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            jitter: 0.2,
+        }
+    }
+
+    pub fn set_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = Self::sanitize_jitter(jitter);
+        self
+    }
+
+    pub fn base(&self) -> Duration {
+        self.base
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    fn sanitize_jitter(jitter: f64) -> f64 {
+        if jitter.is_nan() {
+            0.0
+        } else {
+            jitter.clamp(0.0, 1.0)
+        }
+    }
+
+    /// 第 attempt 次(从 0 开始)重试前的等待时间
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base
+            .saturating_mul(2u32.saturating_pow(attempt.min(16)))
+            .min(self.max);
+        let jitter = Self::sanitize_jitter(self.jitter);
+        if jitter <= 0.0 || delay.is_zero() {
+            return delay;
+        }
+        let rate = rand::thread_rng().gen_range(-jitter..=jitter);
+        delay.mul_f64(1.0 + rate)
+    }
+}
+
+/// 请求类型,用于单独设置超时时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestOperation {
+    GetConfig,
+    PublishConfig,
+    RemoveConfig,
+    ListenConfig,
+    RegisterInstance,
+    UnregisterInstance,
+    Subscribe,
+    QueryInstance,
+    Heartbeat,
+}
+
+impl RequestOperation {
+    /// 重复执行不会改变结果的请求;发布配置、注册实例等请求可能已在服务端生效,网络错误时不自动重试
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Self::PublishConfig | Self::RegisterInstance)
+    }
+}
+
+/// 连接策略:熔断、重连退避、请求重试与超时
+#[derive(Debug, Clone)]
+pub struct ConnectionPolicy {
+    /// 单个服务地址的熔断配置
+    pub breaker: BreakerConfig,
+    /// 连接不可用时切换地址的等待时间,连续失败时指数增长
+    pub reconnect_backoff: BackoffConfig,
+    /// 单个请求因网络错误失败后的最大重试次数,服务端返回的业务错误与非幂等请求不重试
+    pub max_retries: u32,
+    pub retry_backoff: BackoffConfig,
+    /// 请求默认超时时间
+    pub request_timeout: Duration,
+    /// 按请求类型覆盖超时时间
+    pub operation_timeouts: HashMap<RequestOperation, Duration>,
+}
+
+impl Default for ConnectionPolicy {
+    fn default() -> Self {
+        Self {
+            breaker: Default::default(),
+            reconnect_backoff: BackoffConfig::new(
+                Duration::from_millis(1000),
+                Duration::from_secs(30),
+            ),
+            max_retries: 0,
+            retry_backoff: BackoffConfig::new(Duration::from_millis(200), Duration::from_secs(3)),
+            request_timeout: Duration::from_millis(3000),
+            operation_timeouts: Default::default(),
+        }
+    }
+}
+
+impl ConnectionPolicy {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_breaker(mut self, breaker: BreakerConfig) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    pub fn set_reconnect_backoff(mut self, backoff: BackoffConfig) -> Self {
+        self.reconnect_backoff = backoff;
+        self
+    }
+
+    pub fn set_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn set_retry_backoff(mut self, backoff: BackoffConfig) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    pub fn set_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn set_operation_timeout(mut self, operation: RequestOperation, timeout: Duration) -> Self {
+        self.operation_timeouts.insert(operation, timeout);
+        self
+    }
+
+    pub fn timeout(&self, operation: RequestOperation) -> Duration {
+        self.operation_timeouts
+            .get(&operation)
+            .copied()
+            .unwrap_or(self.request_timeout)
+    }
+
+    pub(crate) fn timeout_millis(&self, operation: RequestOperation) -> u64 {
+        self.timeout(operation).as_millis() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff =
+            BackoffConfig::new(Duration::from_millis(100), Duration::from_secs(1)).set_jitter(0.0);
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(400));
+        assert_eq!(backoff.delay(10), Duration::from_secs(1));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+
+        let backoff = backoff.set_jitter(0.5);
+        for _ in 0..20 {
+            let delay = backoff.delay(1);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(300));
+        }
+    }
+
+    #[test]
+    fn test_backoff_invalid_jitter() {
+        let base = BackoffConfig::new(Duration::from_millis(100), Duration::from_secs(1));
+        let backoff = base.clone().set_jitter(f64::NAN);
+        assert_eq!(backoff.jitter(), 0.0);
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+
+        let backoff = base.set_jitter(5.0);
+        assert_eq!(backoff.jitter(), 1.0);
+        for _ in 0..20 {
+            assert!(backoff.delay(0) <= Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn test_idempotent_operation() {
+        assert!(RequestOperation::GetConfig.is_idempotent());
+        assert!(RequestOperation::Subscribe.is_idempotent());
+        assert!(!RequestOperation::PublishConfig.is_idempotent());
+        assert!(!RequestOperation::RegisterInstance.is_idempotent());
+    }
+
+    #[test]
+    fn test_operation_timeout() {
+        let policy = ConnectionPolicy::new()
+            .set_operation_timeout(RequestOperation::ListenConfig, Duration::from_secs(10));
+        assert_eq!(
+            policy.timeout(RequestOperation::ListenConfig),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            policy.timeout(RequestOperation::GetConfig),
+            Duration::from_millis(3000)
+        );
+    }
+}
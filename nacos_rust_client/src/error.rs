@@ -0,0 +1,209 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// 客户端公开接口的错误类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// 配置或服务实例不存在
+    NotFound(String),
+    /// 未登录、token 失效或没有权限
+    Unauthorized(String),
+    /// 请求超时
+    Timeout(String),
+    /// 服务端返回错误;code 为 http 状态码或 gRPC 响应中的 errorCode
+    ServerError { code: u16, message: String },
+    /// 连接失败、连接已关闭等传输层错误
+    Transport(String),
+    /// 响应或配置内容解析失败
+    Decode(String),
+    /// 其它错误,如参数不合法
+    Other(String),
+}
+
+impl Error {
+    /// 按 http 状态码转换
+    pub fn from_http_status(status: u16, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match status {
+            404 => Self::NotFound(message),
+            401 | 403 => Self::Unauthorized(message),
+            408 | 504 => Self::Timeout(message),
+            _ => Self::ServerError {
+                code: status,
+                message,
+            },
+        }
+    }
+
+    /// 按 gRPC 响应中的 errorCode 转换
+    pub fn from_error_code(error_code: u16, message: Option<String>) -> Self {
+        let message = message.unwrap_or_default();
+        match error_code {
+            404 => Self::NotFound(message),
+            401 | 403 => Self::Unauthorized(message),
+            _ => Self::ServerError {
+                code: error_code,
+                message,
+            },
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::NotFound(_))
+    }
+
+    /// 服务端已正常响应,只是返回了业务错误;用于区分服务端不可用
+    pub fn is_server_reply(&self) -> bool {
+        matches!(
+            self,
+            Self::NotFound(_) | Self::Unauthorized(_) | Self::ServerError { .. }
+        )
+    }
+
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, Self::Unauthorized(_))
+    }
+
+    /// 服务端返回 403 或 gRPC 鉴权错误码,需要重新登录
+    pub(crate) fn anyhow_is_unauthorized(err: &anyhow::Error) -> bool {
+        err.chain()
+            .any(|e| Self::from_std_error(e).is_some_and(|e| e.is_unauthorized()))
+    }
+
+    pub(crate) fn anyhow_is_not_found(err: &anyhow::Error) -> bool {
+        err.chain()
+            .any(|e| Self::from_std_error(e).is_some_and(|e| e.is_not_found()))
+    }
+
+    pub(crate) fn anyhow_is_server_reply(err: &anyhow::Error) -> bool {
+        err.downcast_ref::<Self>()
+            .map(|e| e.is_server_reply())
+            .unwrap_or(false)
+    }
+
+    fn from_std_error(err: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if let Some(e) = err.downcast_ref::<Self>() {
+            return Some(e.clone());
+        }
+        if let Some(e) = err.downcast_ref::<reqwest::Error>() {
+            return Some(if e.is_timeout() {
+                Self::Timeout(e.to_string())
+            } else if e.is_decode() {
+                Self::Decode(e.to_string())
+            } else if let Some(status) = e.status() {
+                Self::from_http_status(status.as_u16(), e.to_string())
+            } else {
+                Self::Transport(e.to_string())
+            });
+        }
+        if let Some(e) = err.downcast_ref::<tonic::Status>() {
+            return Some(match e.code() {
+                tonic::Code::DeadlineExceeded => Self::Timeout(e.to_string()),
+                tonic::Code::Unauthenticated | tonic::Code::PermissionDenied => {
+                    Self::Unauthorized(e.to_string())
+                }
+                tonic::Code::NotFound => Self::NotFound(e.to_string()),
+                _ => Self::Transport(e.to_string()),
+            });
+        }
+        if let Some(e) = err.downcast_ref::<std::io::Error>() {
+            return Some(match e.kind() {
+                std::io::ErrorKind::TimedOut => Self::Timeout(e.to_string()),
+                _ => Self::Transport(e.to_string()),
+            });
+        }
+        if err.is::<tokio::time::error::Elapsed>() {
+            return Some(Self::Timeout(err.to_string()));
+        }
+        if err.is::<tonic::transport::Error>() {
+            return Some(Self::Transport(err.to_string()));
+        }
+        if err.is::<serde_json::Error>()
+            || err.is::<serde_yaml::Error>()
+            || err.is::<toml::de::Error>()
+            || err.is::<serde_urlencoded::de::Error>()
+            || err.is::<std::string::FromUtf8Error>()
+        {
+            return Some(Self::Decode(err.to_string()));
+        }
+        None
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(msg) => write!(f, "not found: {}", msg),
+            Self::Unauthorized(msg) => write!(f, "unauthorized: {}", msg),
+            Self::Timeout(msg) => write!(f, "timeout: {}", msg),
+            Self::ServerError { code, message } => {
+                write!(f, "server error, code:{}, message:{}", code, message)
+            }
+            Self::Transport(msg) => write!(f, "transport error: {}", msg),
+            Self::Decode(msg) => write!(f, "decode error: {}", msg),
+            Self::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(e) = Self::from_std_error(cause) {
+                return e;
+            }
+        }
+        Self::Other(format!("{:#}", err))
+    }
+}
+
+impl From<actix::MailboxError> for Error {
+    fn from(err: actix::MailboxError) -> Self {
+        match err {
+            actix::MailboxError::Timeout => Self::Timeout(err.to_string()),
+            actix::MailboxError::Closed => Self::Transport(err.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::TimedOut => Self::Timeout(err.to_string()),
+            _ => Self::Transport(err.to_string()),
+        }
+    }
+}
+
+impl From<tokio::sync::oneshot::error::RecvError> for Error {
+    fn from(err: tokio::sync::oneshot::error::RecvError) -> Self {
+        Self::Transport(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_from_anyhow() {
+        let err: anyhow::Error =
+            Error::from_error_code(404, Some("config not found".into())).into();
+        assert!(Error::anyhow_is_server_reply(&err));
+        assert!(!Error::anyhow_is_unauthorized(&err));
+        assert!(Error::from(err).is_not_found());
+        let err = anyhow::Error::new(tonic::Status::permission_denied("token expired"));
+        assert!(Error::anyhow_is_unauthorized(&err));
+        assert_eq!(
+            Error::from_http_status(403, "no right"),
+            Error::Unauthorized("no right".to_owned())
+        );
+        let err = anyhow::Error::new(serde_json::from_str::<u32>("x").unwrap_err());
+        assert!(matches!(Error::from(err), Error::Decode(_)));
+        let err = anyhow::anyhow!("something wrong");
+        assert_eq!(Error::from(err), Error::Other("something wrong".to_owned()));
+    }
+}
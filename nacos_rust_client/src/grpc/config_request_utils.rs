@@ -1,5 +1,6 @@
 use actix::Addr;
 use std::sync::Arc;
+use std::time::Duration;
 use tonic::transport::Channel;
 
 use super::{
@@ -8,14 +9,18 @@ use super::{
         ConfigListenContext, ConfigPublishRequest, ConfigQueryRequest, ConfigQueryResponse,
         ConfigRemoveRequest,
     },
-    build_request_payload, do_timeout_request,
+    build_request_payload, do_timeout_request, do_timeout_request_with_duration,
 };
 use crate::client::auth::AuthActor;
-use crate::client::ClientInfo;
+use crate::client::{AuthResource, ClientInfo};
+use crate::error::Error;
 use crate::{
-    client::{config_client::ConfigKey, get_md5, now_millis},
+    client::{
+        config_client::{model::is_cas_conflict_message, ConfigKey},
+        get_md5, now_millis,
+    },
     conn_manage::conn_msg::ConfigResponse,
-    grpc::constant::LABEL_MODULE_CONFIG,
+    grpc::constant::{CONFIG_NOT_FOUND, LABEL_MODULE_CONFIG},
 };
 
 pub(crate) struct GrpcConfigRequestUtils;
@@ -36,8 +41,14 @@ impl GrpcConfigRequestUtils {
             request_id: Some(check_id),
             ..Default::default()
         };
-        let payload =
-            build_request_payload("ConfigQueryRequest", &request, &auth_addr, &client_info).await?;
+        let payload = build_request_payload(
+            "ConfigQueryRequest",
+            &request,
+            AuthResource::config(&request.tenant, &request.group),
+            &auth_addr,
+            &client_info,
+        )
+        .await?;
         let payload = do_timeout_request(channel, payload).await?;
         //debug
         //log::info!("check_register,{}",&PayloadUtils::get_payload_string(&payload));
@@ -56,6 +67,7 @@ impl GrpcConfigRequestUtils {
         config_key: ConfigKey,
         auth_addr: Addr<AuthActor>,
         client_info: Arc<ClientInfo>,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<ConfigResponse> {
         let request = ConfigQueryRequest {
             data_id: config_key.data_id,
@@ -65,9 +77,15 @@ impl GrpcConfigRequestUtils {
             request_id,
             ..Default::default()
         };
-        let payload =
-            build_request_payload("ConfigQueryRequest", &request, &auth_addr, &client_info).await?;
-        let payload = do_timeout_request(channel, payload).await?;
+        let payload = build_request_payload(
+            "ConfigQueryRequest",
+            &request,
+            AuthResource::config(&request.tenant, &request.group),
+            &auth_addr,
+            &client_info,
+        )
+        .await?;
+        let payload = do_timeout_request_with_duration(channel, payload, timeout).await?;
         //debug
         //log::info!("config_query,{}",&PayloadUtils::get_payload_string(&payload));
         let body_vec = payload.body.unwrap_or_default().value;
@@ -75,45 +93,62 @@ impl GrpcConfigRequestUtils {
         if response.result_code != 200u16 {
             log::warn!(
                 "config_query response error,{}",
-                String::from_utf8(body_vec)?
+                String::from_utf8_lossy(&body_vec)
             );
-            return Err(anyhow::anyhow!("response error code"));
+            if response.error_code == CONFIG_NOT_FOUND {
+                let message = response.message.unwrap_or_default();
+                return Err(Error::NotFound(message).into());
+            }
+            return Err(Error::from_error_code(response.error_code, response.message).into());
         }
         let md5 = response.md5.unwrap_or_else(|| get_md5(&response.content));
         Ok(ConfigResponse::ConfigValue(response.content, md5))
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn config_publish(
         channel: Channel,
         request_id: Option<String>,
         config_key: ConfigKey,
         content: String,
+        cas_md5: Option<String>,
         auth_addr: Addr<AuthActor>,
         client_info: Arc<ClientInfo>,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<ConfigResponse> {
+        let is_cas = cas_md5.is_some();
         let request = ConfigPublishRequest {
             data_id: config_key.data_id,
             group: config_key.group,
             tenant: config_key.tenant,
             content,
+            cas_md5,
             request_id,
             module: Some(LABEL_MODULE_CONFIG.to_owned()),
             ..Default::default()
         };
-        let payload =
-            build_request_payload("ConfigPublishRequest", &request, &auth_addr, &client_info)
-                .await?;
-        let payload = do_timeout_request(channel, payload).await?;
+        let payload = build_request_payload(
+            "ConfigPublishRequest",
+            &request,
+            AuthResource::config(&request.tenant, &request.group),
+            &auth_addr,
+            &client_info,
+        )
+        .await?;
+        let payload = do_timeout_request_with_duration(channel, payload, timeout).await?;
         //debug
         //log::info!("config_publish,{}",&PayloadUtils::get_payload_string(&payload));
         let body_vec = payload.body.unwrap_or_default().value;
         let response: BaseResponse = serde_json::from_slice(&body_vec)?;
         if response.result_code != 200u16 {
+            if is_cas && is_cas_conflict_message(response.message.as_deref().unwrap_or_default()) {
+                return Ok(ConfigResponse::CasConflict);
+            }
             log::warn!(
                 "config_publish response error,{}",
-                String::from_utf8(body_vec)?
+                String::from_utf8_lossy(&body_vec)
             );
-            return Err(anyhow::anyhow!("response error code"));
+            return Err(Error::from_error_code(response.error_code, response.message).into());
         }
         Ok(ConfigResponse::None)
     }
@@ -124,6 +159,7 @@ impl GrpcConfigRequestUtils {
         config_key: ConfigKey,
         auth_addr: Addr<AuthActor>,
         client_info: Arc<ClientInfo>,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<ConfigResponse> {
         let request = ConfigRemoveRequest {
             data_id: config_key.data_id,
@@ -133,10 +169,15 @@ impl GrpcConfigRequestUtils {
             request_id,
             ..Default::default()
         };
-        let payload =
-            build_request_payload("ConfigRemoveRequest", &request, &auth_addr, &client_info)
-                .await?;
-        let payload = do_timeout_request(channel, payload).await?;
+        let payload = build_request_payload(
+            "ConfigRemoveRequest",
+            &request,
+            AuthResource::config(&request.tenant, &request.group),
+            &auth_addr,
+            &client_info,
+        )
+        .await?;
+        let payload = do_timeout_request_with_duration(channel, payload, timeout).await?;
         //debug
         //log::info!("config_remove,{}",&PayloadUtils::get_payload_string(&payload));
         let body_vec = payload.body.unwrap_or_default().value;
@@ -144,9 +185,9 @@ impl GrpcConfigRequestUtils {
         if response.result_code != 200u16 {
             log::warn!(
                 "config_remove response error,{}",
-                String::from_utf8(body_vec)?
+                String::from_utf8_lossy(&body_vec)
             );
-            return Err(anyhow::anyhow!("response error code"));
+            return Err(Error::from_error_code(response.error_code, response.message).into());
         }
         Ok(ConfigResponse::None)
     }
@@ -158,6 +199,7 @@ impl GrpcConfigRequestUtils {
         listen: bool,
         auth_addr: Addr<AuthActor>,
         client_info: Arc<ClientInfo>,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<ConfigResponse> {
         let config_listen_contexts: Vec<ConfigListenContext> = listen_items
             .into_iter()
@@ -179,11 +221,12 @@ impl GrpcConfigRequestUtils {
         let payload = build_request_payload(
             "ConfigBatchListenRequest",
             &request,
+            AuthResource::config("", ""),
             &auth_addr,
             &client_info,
         )
         .await?;
-        let payload = do_timeout_request(channel, payload).await?;
+        let payload = do_timeout_request_with_duration(channel, payload, timeout).await?;
         //debug
         //log::info!("config_change_batch_listen,{}",&PayloadUtils::get_payload_string(&payload));
         let body_vec = payload.body.unwrap_or_default().value;
@@ -191,9 +234,9 @@ impl GrpcConfigRequestUtils {
         if response.result_code != 200u16 {
             log::warn!(
                 "config_change_batch_listen response error,{}",
-                String::from_utf8(body_vec)?
+                String::from_utf8_lossy(&body_vec)
             );
-            return Err(anyhow::anyhow!("response error code"));
+            return Err(Error::from_error_code(response.error_code, response.message).into());
         }
         let keys: Vec<ConfigKey> = response
             .changed_configs
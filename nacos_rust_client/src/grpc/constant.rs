@@ -4,3 +4,6 @@ pub const LABEL_SOURCE_CLUSTER: &str = "cluster";
 pub const LABEL_MODULE: &str = "module";
 pub const LABEL_MODULE_CONFIG: &str = "config";
 pub const LABEL_MODULE_NAMING: &str = "naming";
+
+/// 查询配置时表示配置不存在的 errorCode
+pub const CONFIG_NOT_FOUND: u16 = 300;
@@ -5,7 +5,10 @@ use tokio_stream::StreamExt;
 use tonic::transport::Channel;
 
 use super::{
-    api_model::{ConfigChangeNotifyRequest, NotifySubscriberRequest},
+    api_model::{
+        ClientDetectionRequest, ConfigChangeNotifyRequest, ConnectResetRequest,
+        NotifySubscriberRequest,
+    },
     config_request_utils::GrpcConfigRequestUtils,
     constant::*,
     nacos_proto::{
@@ -15,7 +18,9 @@ use super::{
 };
 use crate::client::auth::AuthActor;
 use crate::{
-    client::{config_client::ConfigKey, naming_client::ServiceInstanceKey, ClientInfo},
+    client::{
+        config_client::ConfigKey, naming_client::ServiceInstanceKey, ClientInfo, ConnectionPolicy,
+    },
     conn_manage::{
         conn_msg::{
             ConfigRequest, ConfigResponse, ConnCallbackMsg, NamingRequest, NamingResponse,
@@ -23,6 +28,7 @@ use crate::{
         },
         manage::{ConnManage, ConnManageCmd},
     },
+    error::Error,
     grpc::{
         api_model::{BaseResponse, ConnectionSetupRequest},
         channel::CloseableChannel,
@@ -49,6 +55,7 @@ pub struct InnerGrpcClient {
     error_time: u8,
     client_info: Arc<ClientInfo>,
     auth_addr: Addr<AuthActor>,
+    policy: Arc<ConnectionPolicy>,
 }
 
 impl InnerGrpcClient {
@@ -85,9 +92,15 @@ impl InnerGrpcClient {
             error_time: 0,
             client_info,
             auth_addr,
+            policy: Default::default(),
         })
     }
 
+    pub(crate) fn set_policy(mut self, policy: Arc<ConnectionPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
     fn next_request_id(&mut self) -> String {
         if self.request_id >= 0x7fff_ffff_ffff_ffff {
             self.request_id = 0;
@@ -159,7 +172,7 @@ impl InnerGrpcClient {
                         tokio::time::sleep(Duration::from_millis(100)).await;
                     }
                     Err(err) => {
-                        log::error!("check_register error,{}", err.to_string());
+                        log::error!("check_register error,{}", err);
                         return false;
                     }
                 }
@@ -227,20 +240,29 @@ impl InnerGrpcClient {
     ) -> anyhow::Result<()> {
         //debug
         //log::info!( "config change notify:{}#{}#{}", &config_key.data_id, &config_key.group, &config_key.tenant);
-        if let ConfigResponse::ConfigValue(content, md5) = GrpcConfigRequestUtils::config_query(
+        let msg = match GrpcConfigRequestUtils::config_query(
             channel,
             Some(request_id),
             config_key.clone(),
             auth_addr,
             client_info,
+            None,
         )
-        .await?
+        .await
         {
-            let msg = ConnCallbackMsg::ConfigChange(config_key, content, md5);
-            if let Some(addr) = manage_addr.upgrade() {
-                addr.do_send(msg);
+            Ok(ConfigResponse::ConfigValue(content, md5)) => {
+                ConnCallbackMsg::ConfigChange(config_key, content, md5)
             }
+            //配置已被删除
+            Err(err) if Error::anyhow_is_not_found(&err) => {
+                ConnCallbackMsg::ConfigDelete(config_key)
+            }
+            Ok(_) => return Ok(()),
+            Err(err) => return Err(err),
         };
+        if let Some(addr) = manage_addr.upgrade() {
+            addr.do_send(msg);
+        }
         Ok(())
     }
 
@@ -255,6 +277,7 @@ impl InnerGrpcClient {
         let manage_addr = self.manage_addr.clone();
         let auth_addr = self.auth_addr.clone();
         let client_info = self.client_info.clone();
+        let id = self.id;
         async move {
             let mut stream_id = 0u128;
             while let Some(item) = receiver_stream.next().await {
@@ -320,6 +343,45 @@ impl InnerGrpcClient {
                                     log::error!("NotifySubscriberRequest error {}", e);
                                 }
                             };
+                        } else if t == "ClientDetectionRequest" {
+                            match serde_json::from_slice::<ClientDetectionRequest>(&body_vec) {
+                                Ok(request) => {
+                                    let response =
+                                        BaseResponse::build_with_request_id(request.request_id);
+                                    let val = serde_json::to_string(&response).unwrap();
+                                    let res_payload =
+                                        PayloadUtils::build_payload("ClientDetectionResponse", val);
+                                    tx.send(Some(res_payload)).await.ok();
+                                }
+                                Err(e) => {
+                                    log::error!("ClientDetectionRequest error {}", e);
+                                }
+                            };
+                        } else if t == "ConnectResetRequest" {
+                            match serde_json::from_slice::<ConnectResetRequest>(&body_vec) {
+                                Ok(request) => {
+                                    let response =
+                                        BaseResponse::build_with_request_id(request.request_id);
+                                    let val = serde_json::to_string(&response).unwrap();
+                                    let res_payload =
+                                        PayloadUtils::build_payload("ConnectResetResponse", val);
+                                    tx.send(Some(res_payload)).await.ok();
+                                    //服务端建议切换的地址,端口无效时交由连接管理重新选择
+                                    let server = match (request.server_ip, request.server_port) {
+                                        (Some(ip), Some(port)) if !ip.is_empty() => {
+                                            port.parse::<u32>().ok().map(|port| (ip, port))
+                                        }
+                                        _ => None,
+                                    };
+                                    if let Some(manage_addr) = manage_addr.upgrade() {
+                                        manage_addr
+                                            .do_send(ConnManageCmd::ConnectReset { id, server });
+                                    }
+                                }
+                                Err(e) => {
+                                    log::error!("ConnectResetRequest error {}", e);
+                                }
+                            };
                         }
                     }
                 } else {
@@ -405,6 +467,8 @@ impl InnerGrpcClient {
                 ServiceResult {
                     hosts,
                     cache_millis: Some(service_info.cache_millis as u64),
+                    protect_threshold: None,
+                    reach_protection_threshold: service_info.reach_protection_threshold,
                 },
             )
         } else {
@@ -445,6 +509,7 @@ pub enum InnerGrpcClientCmd {
     ReceiverStreamItem(Payload),
     Request(Payload, Option<PayloadSenderType>),
     Ping,
+    Close,
 }
 
 pub enum InnerGrpcClientResult {
@@ -462,6 +527,21 @@ impl Handler<InnerGrpcClientCmd> for InnerGrpcClient {
                 self.do_request(ctx, payload, sender);
                 Ok(InnerGrpcClientResult::None)
             }
+            InnerGrpcClientCmd::Close => {
+                log::info!("InnerGrpcClient close");
+                //结束请求流,再停止actor
+                if let Some(tx) = self.stream_sender.clone() {
+                    async move {
+                        tx.send(None).await.ok();
+                    }
+                    .into_actor(self)
+                    .map(|_, _, ctx| ctx.stop())
+                    .wait(ctx);
+                } else {
+                    ctx.stop();
+                }
+                Ok(InnerGrpcClientResult::None)
+            }
         }
     }
 }
@@ -470,6 +550,7 @@ impl Handler<ConfigRequest> for InnerGrpcClient {
     type Result = ResponseActFuture<Self, anyhow::Result<ConfigResponse>>;
 
     fn handle(&mut self, config_request: ConfigRequest, _ctx: &mut Self::Context) -> Self::Result {
+        let timeout = Some(self.policy.timeout(config_request.operation()));
         let channel = self.channel.clone();
         let manage_addr = self.manage_addr.clone();
         let conn_reader = self.conn_reader;
@@ -490,6 +571,7 @@ impl Handler<ConfigRequest> for InnerGrpcClient {
                         config_key,
                         auth_addr,
                         client_info,
+                        timeout,
                     )
                     .await
                 }
@@ -499,8 +581,10 @@ impl Handler<ConfigRequest> for InnerGrpcClient {
                         Some(request_id),
                         config_key.clone(),
                         content,
+                        None,
                         auth_addr,
                         client_info,
+                        timeout,
                     )
                     .await
                     /*
@@ -513,6 +597,19 @@ impl Handler<ConfigRequest> for InnerGrpcClient {
                     .ok();
                     */
                 }
+                ConfigRequest::CasSetConfig(config_key, content, cas_md5) => {
+                    GrpcConfigRequestUtils::config_publish(
+                        channel,
+                        Some(request_id),
+                        config_key,
+                        content,
+                        Some(cas_md5),
+                        auth_addr,
+                        client_info,
+                        timeout,
+                    )
+                    .await
+                }
                 ConfigRequest::DeleteConfig(config_key) => {
                     GrpcConfigRequestUtils::config_remove(
                         channel.clone(),
@@ -520,6 +617,7 @@ impl Handler<ConfigRequest> for InnerGrpcClient {
                         config_key.clone(),
                         auth_addr,
                         client_info,
+                        timeout,
                     )
                     .await
                     /*
@@ -542,6 +640,7 @@ impl Handler<ConfigRequest> for InnerGrpcClient {
                         listen,
                         auth_addr.clone(),
                         client_info.clone(),
+                        timeout,
                     )
                     .await?;
                     if let ConfigResponse::ChangeKeys(keys) = res {
@@ -572,6 +671,7 @@ impl Handler<NamingRequest> for InnerGrpcClient {
     type Result = ResponseActFuture<Self, anyhow::Result<NamingResponse>>;
 
     fn handle(&mut self, request: NamingRequest, _ctx: &mut Self::Context) -> Self::Result {
+        let timeout = Some(self.policy.timeout(request.operation()));
         let channel = self.channel.clone();
         let conn_reader = self.conn_reader;
         let manage_addr = self.manage_addr.clone();
@@ -591,6 +691,7 @@ impl Handler<NamingRequest> for InnerGrpcClient {
                         true,
                         auth_addr,
                         client_info,
+                        timeout,
                     )
                     .await
                 }
@@ -601,6 +702,19 @@ impl Handler<NamingRequest> for InnerGrpcClient {
                         false,
                         auth_addr,
                         client_info,
+                        timeout,
+                    )
+                    .await
+                }
+                NamingRequest::Update(instance) => {
+                    //2.x 协议中重复注册即为更新
+                    GrpcNamingRequestUtils::instance_register(
+                        channel,
+                        instance,
+                        true,
+                        auth_addr,
+                        client_info,
+                        timeout,
                     )
                     .await
                 }
@@ -610,6 +724,7 @@ impl Handler<NamingRequest> for InnerGrpcClient {
                         instances,
                         auth_addr,
                         client_info,
+                        timeout,
                     )
                     .await
                 }
@@ -623,6 +738,7 @@ impl Handler<NamingRequest> for InnerGrpcClient {
                             Some("".to_owned()),
                             auth_addr.clone(),
                             client_info.clone(),
+                            timeout,
                         )
                         .await;
                         if let Ok(NamingResponse::ServiceResult(service_result)) = &res {
@@ -646,6 +762,7 @@ impl Handler<NamingRequest> for InnerGrpcClient {
                             Some("".to_owned()),
                             auth_addr.clone(),
                             client_info.clone(),
+                            timeout,
                         )
                         .await;
                     }
@@ -665,6 +782,7 @@ impl Handler<NamingRequest> for InnerGrpcClient {
                         Some(param.healthy_only),
                         auth_addr,
                         client_info,
+                        timeout,
                     )
                     .await
                 }
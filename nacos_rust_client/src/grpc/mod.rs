@@ -1,11 +1,10 @@
 use self::nacos_proto::request_client::RequestClient;
-use crate::client::auth::{get_token_result, AuthActor};
-use crate::client::ClientInfo;
+use crate::client::auth::{get_auth_headers, get_token_result, AuthActor};
+use crate::client::{AuthResource, ClientInfo};
 use crate::grpc::nacos_proto::Payload;
 use crate::grpc::utils::PayloadUtils;
 use actix::Addr;
 use serde::Serialize;
-use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::timeout;
@@ -64,11 +63,12 @@ pub async fn do_timeout_request_with_duration(
 pub(crate) async fn build_request_payload<T: Serialize>(
     request_type: &str,
     request: &T,
+    resource: AuthResource,
     auth_addr: &Addr<AuthActor>,
     client_info: &Arc<ClientInfo>,
 ) -> anyhow::Result<Payload> {
     let val = serde_json::to_string(request)?;
-    let mut payload_header = HashMap::new();
+    let mut payload_header = get_auth_headers(auth_addr, resource).await?;
     let token = get_token_result(auth_addr).await?;
     payload_header.insert(ACCESS_TOKEN_HEADER.to_string(), token.as_ref().to_owned());
     let payload =
@@ -1,5 +1,6 @@
 use actix::Addr;
 use std::sync::Arc;
+use std::time::Duration;
 use tonic::transport::Channel;
 
 use super::{
@@ -7,10 +8,11 @@ use super::{
         BaseResponse, BatchInstanceRequest, Instance as ApiInstance, ServiceQueryRequest,
         ServiceQueryResponse, SubscribeServiceRequest, SubscribeServiceResponse,
     },
-    build_request_payload, do_timeout_request,
+    build_request_payload, do_timeout_request_with_duration,
 };
 use crate::client::auth::AuthActor;
-use crate::client::ClientInfo;
+use crate::client::{AuthResource, ClientInfo};
+use crate::error::Error;
 use crate::{
     client::naming_client::{Instance, ServiceInstanceKey},
     conn_manage::conn_msg::{NamingResponse, ServiceResult},
@@ -61,12 +63,25 @@ impl GrpcNamingRequestUtils {
         }
     }
 
+    fn auth_resource(
+        namespace: &Option<String>,
+        group_name: &Option<String>,
+        service_name: &Option<String>,
+    ) -> AuthResource {
+        AuthResource::naming(
+            namespace.as_deref().unwrap_or_default(),
+            group_name.as_deref().unwrap_or_default(),
+            service_name.as_deref().unwrap_or_default(),
+        )
+    }
+
     pub async fn instance_register(
         channel: Channel,
         instance: Instance,
         is_reqister: bool,
         auth_addr: Addr<AuthActor>,
         client_info: Arc<ClientInfo>,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<NamingResponse> {
         let request = InstanceRequest {
             namespace: Some(instance.namespace_id.to_owned()),
@@ -81,11 +96,32 @@ impl GrpcNamingRequestUtils {
             module: Some(LABEL_MODULE_NAMING.to_owned()),
             ..Default::default()
         };
-        let payload =
-            build_request_payload("InstanceRequest", &request, &auth_addr, &client_info).await?;
+        //持久化实例使用 PersistentInstanceRequest
+        let request_type = if request
+            .instance
+            .as_ref()
+            .map(|e| e.ephemeral)
+            .unwrap_or(true)
+        {
+            "InstanceRequest"
+        } else {
+            "PersistentInstanceRequest"
+        };
+        let payload = build_request_payload(
+            request_type,
+            &request,
+            Self::auth_resource(
+                &request.namespace,
+                &request.group_name,
+                &request.service_name,
+            ),
+            &auth_addr,
+            &client_info,
+        )
+        .await?;
         //debug
         //log::info!("instance_register request,{}",&PayloadUtils::get_payload_string(&payload));
-        let payload = do_timeout_request(channel, payload).await?;
+        let payload = do_timeout_request_with_duration(channel, payload, timeout).await?;
         //debug
         //log::info!("instance_register,{}",&PayloadUtils::get_payload_string(&payload));
         let body_vec = payload.body.unwrap_or_default().value;
@@ -93,9 +129,9 @@ impl GrpcNamingRequestUtils {
         if res.result_code != 200u16 {
             log::warn!(
                 "instance_register response error,{}",
-                String::from_utf8(body_vec)?
+                String::from_utf8_lossy(&body_vec)
             );
-            return Err(anyhow::anyhow!("response error code"));
+            return Err(Error::from_error_code(res.error_code, res.message).into());
         }
         Ok(NamingResponse::None)
     }
@@ -105,9 +141,10 @@ impl GrpcNamingRequestUtils {
         instances: Vec<Instance>,
         auth_addr: Addr<AuthActor>,
         client_info: Arc<ClientInfo>,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<NamingResponse> {
         if instances.is_empty() {
-            return Err(anyhow::anyhow!("register instances is empty"));
+            return Err(Error::Other("register instances is empty".to_owned()).into());
         }
         let first_instance = instances.first().unwrap();
         let mut request = BatchInstanceRequest {
@@ -124,10 +161,19 @@ impl GrpcNamingRequestUtils {
             .collect::<Vec<_>>();
         request.instances = Some(api_instances);
 
-        let payload =
-            build_request_payload("BatchInstanceRequest", &request, &auth_addr, &client_info)
-                .await?;
-        let payload = do_timeout_request(channel, payload).await?;
+        let payload = build_request_payload(
+            "BatchInstanceRequest",
+            &request,
+            Self::auth_resource(
+                &request.namespace,
+                &request.group_name,
+                &request.service_name,
+            ),
+            &auth_addr,
+            &client_info,
+        )
+        .await?;
+        let payload = do_timeout_request_with_duration(channel, payload, timeout).await?;
         //debug
         //log::info!("batch_register,{}",&PayloadUtils::get_payload_string(&payload));
         let body_vec = payload.body.unwrap_or_default().value;
@@ -135,9 +181,9 @@ impl GrpcNamingRequestUtils {
         if res.result_code != 200u16 {
             log::warn!(
                 "batch_register response error,{}",
-                String::from_utf8(body_vec)?
+                String::from_utf8_lossy(&body_vec)
             );
-            return Err(anyhow::anyhow!("response error code"));
+            return Err(Error::from_error_code(res.error_code, res.message).into());
         }
         Ok(NamingResponse::None)
     }
@@ -149,6 +195,7 @@ impl GrpcNamingRequestUtils {
         clusters: Option<String>,
         auth_addr: Addr<AuthActor>,
         client_info: Arc<ClientInfo>,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<NamingResponse> {
         let clone_key = service_key.clone();
         let request = SubscribeServiceRequest {
@@ -163,18 +210,26 @@ impl GrpcNamingRequestUtils {
         let payload = build_request_payload(
             "SubscribeServiceRequest",
             &request,
+            Self::auth_resource(
+                &request.namespace,
+                &request.group_name,
+                &request.service_name,
+            ),
             &auth_addr,
             &client_info,
         )
         .await?;
-        let payload = do_timeout_request(channel, payload).await?;
+        let payload = do_timeout_request_with_duration(channel, payload, timeout).await?;
         //debug
         //log::info!("subscribe,{}",&PayloadUtils::get_payload_string(&payload));
         let body_vec = payload.body.unwrap_or_default().value;
         let res: SubscribeServiceResponse = serde_json::from_slice(&body_vec)?;
         if res.result_code != 200u16 {
-            log::warn!("subscribe response error,{}", String::from_utf8(body_vec)?);
-            return Err(anyhow::anyhow!("response error code"));
+            log::warn!(
+                "subscribe response error,{}",
+                String::from_utf8_lossy(&body_vec)
+            );
+            return Err(Error::from_error_code(res.error_code, res.message).into());
         }
         if let Some(service_info) = res.service_info {
             let hosts = service_info.hosts.unwrap_or_default();
@@ -185,6 +240,8 @@ impl GrpcNamingRequestUtils {
             let service_result = ServiceResult {
                 cache_millis: Some(service_info.cache_millis as u64),
                 hosts,
+                protect_threshold: None,
+                reach_protection_threshold: service_info.reach_protection_threshold,
             };
             Ok(NamingResponse::ServiceResult(service_result))
         } else {
@@ -202,6 +259,7 @@ impl GrpcNamingRequestUtils {
         healthy_only: Option<bool>,
         auth_addr: Addr<AuthActor>,
         client_info: Arc<ClientInfo>,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<NamingResponse> {
         let clone_key = service_key.clone();
         let request = ServiceQueryRequest {
@@ -213,19 +271,28 @@ impl GrpcNamingRequestUtils {
             module: Some(LABEL_MODULE_NAMING.to_owned()),
             ..Default::default()
         };
-        let payload =
-            build_request_payload("ServiceQueryRequest", &request, &auth_addr, &client_info)
-                .await?;
-        let payload = do_timeout_request(channel, payload).await?;
+        let payload = build_request_payload(
+            "ServiceQueryRequest",
+            &request,
+            Self::auth_resource(
+                &request.namespace,
+                &request.group_name,
+                &request.service_name,
+            ),
+            &auth_addr,
+            &client_info,
+        )
+        .await?;
+        let payload = do_timeout_request_with_duration(channel, payload, timeout).await?;
         //log::info!("query_service,{}",&PayloadUtils::get_payload_string(&payload));
         let body_vec = payload.body.unwrap_or_default().value;
         let res: ServiceQueryResponse = serde_json::from_slice(&body_vec)?;
         if res.result_code != 200u16 {
             log::warn!(
                 "query_service response error,{}",
-                String::from_utf8(body_vec)?
+                String::from_utf8_lossy(&body_vec)
             );
-            return Err(anyhow::anyhow!("response error code"));
+            return Err(Error::from_error_code(res.error_code, res.message).into());
         }
         if let Some(service_info) = res.service_info {
             let hosts = service_info.hosts.unwrap_or_default();
@@ -236,6 +303,8 @@ impl GrpcNamingRequestUtils {
             let service_result = ServiceResult {
                 cache_millis: Some(service_info.cache_millis as u64),
                 hosts,
+                protect_threshold: None,
+                reach_protection_threshold: service_info.reach_protection_threshold,
             };
             Ok(NamingResponse::ServiceResult(service_result))
         } else {
@@ -1,12 +1,19 @@
 pub mod client;
 pub mod conn_manage;
+pub mod error;
 pub mod grpc;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+
+pub use error::Error;
 
 pub use client::nacos_client::close_global_system_actor as close_current_system;
 pub use client::nacos_client::get_last_config_client;
 pub use client::nacos_client::get_last_naming_client;
 pub use client::nacos_client::init_global_system_actor;
+pub use client::nacos_client::shutdown_on_signal;
 pub use client::nacos_client::ActixSystemCreateAsyncCmd;
 pub use client::nacos_client::ActixSystemCreateCmd;
 pub use client::nacos_client::ActorCreate;
 pub use client::nacos_client::ActorCreateWrap;
+pub use client::registry::registry;
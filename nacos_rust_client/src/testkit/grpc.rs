@@ -0,0 +1,430 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::StreamExt;
+use tonic::{Response, Status, Streaming};
+
+use super::{normalize_config_key, MockFailure, MockState, ServiceKey};
+use crate::client::config_client::ConfigKey;
+use crate::client::naming_client::{Instance, ServiceInstanceKey};
+use crate::client::{get_md5, now_millis};
+use crate::grpc::api_model::{
+    BaseResponse, BatchInstanceRequest, BatchInstanceResponse, ConfigBatchListenRequest,
+    ConfigChangeBatchListenResponse, ConfigContext, ConfigPublishRequest, ConfigQueryRequest,
+    ConfigQueryResponse, ConfigRemoveRequest, InstanceRequest, InstanceResponse,
+    ServerCheckResponse, ServiceInfo, ServiceQueryRequest, ServiceQueryResponse,
+    SubscribeServiceRequest, SubscribeServiceResponse, ERROR_CODE, SUCCESS_CODE,
+};
+use crate::grpc::constant::CONFIG_NOT_FOUND;
+use crate::grpc::nacos_proto::bi_request_stream_server::{BiRequestStream, BiRequestStreamServer};
+use crate::grpc::nacos_proto::request_server::{Request, RequestServer};
+use crate::grpc::nacos_proto::Payload;
+use crate::grpc::naming_request_utils::GrpcNamingRequestUtils;
+use crate::grpc::utils::PayloadUtils;
+use crate::grpc::ACCESS_TOKEN_HEADER;
+
+pub(crate) async fn serve(listener: tokio::net::TcpListener, state: Arc<MockState>) {
+    let incoming = match tonic::transport::server::TcpIncoming::from_listener(listener, true, None)
+    {
+        Ok(incoming) => incoming,
+        Err(err) => {
+            log::error!("mock grpc server listen error,{}", err);
+            return;
+        }
+    };
+    let service = MockGrpcService { state };
+    tonic::transport::Server::builder()
+        .add_service(RequestServer::new(service.clone()))
+        .add_service(BiRequestStreamServer::new(service))
+        .serve_with_incoming(incoming)
+        .await
+        .ok();
+}
+
+pub(super) fn build_service_info(
+    key: &ServiceKey,
+    instances: Vec<Instance>,
+    reach_protection_threshold: bool,
+) -> ServiceInfo {
+    ServiceInfo {
+        name: Some(key.service_name.clone()),
+        group_name: Some(key.group_name.clone()),
+        cache_millis: 10000,
+        hosts: Some(
+            instances
+                .into_iter()
+                .map(GrpcNamingRequestUtils::convert_to_api_instance)
+                .collect(),
+        ),
+        last_ref_time: now_millis() as i64,
+        reach_protection_threshold,
+        ..Default::default()
+    }
+}
+
+fn service_key(
+    namespace: &Option<String>,
+    group: &Option<String>,
+    name: &Option<String>,
+) -> ServiceKey {
+    ServiceKey::new(
+        namespace.as_deref().unwrap_or_default(),
+        group.as_deref().unwrap_or_default(),
+        name.as_deref().unwrap_or_default(),
+    )
+}
+
+fn to_instance(key: &ServiceKey, input: crate::grpc::api_model::Instance) -> Instance {
+    let service_key = ServiceInstanceKey {
+        namespace_id: Some(key.namespace_id.clone()),
+        group_name: key.group_name.clone(),
+        service_name: key.service_name.clone(),
+    };
+    let mut instance = GrpcNamingRequestUtils::convert_to_instance(input, &service_key);
+    instance.service_name = key.service_name.clone();
+    instance
+}
+
+fn build_response<T: Serialize>(response_type: &str, response: &T) -> Payload {
+    PayloadUtils::build_payload(response_type, serde_json::to_string(response).unwrap())
+}
+
+fn base_response(response_type: &str, request_id: Option<String>) -> Payload {
+    build_response(
+        response_type,
+        &BaseResponse::build_with_request_id(request_id),
+    )
+}
+
+/// 错误响应;包含各类响应的必填字段,客户端按任意响应类型都能解析
+fn error_response(error_code: u16, message: &str, request_id: Option<String>) -> Payload {
+    let response = serde_json::json!({
+        "resultCode": ERROR_CODE,
+        "errorCode": error_code,
+        "message": message,
+        "requestId": request_id,
+        "content": "",
+        "lastModified": 0,
+        "beta": false,
+        "changedConfigs": [],
+    });
+    PayloadUtils::build_payload("ErrorResponse", response.to_string())
+}
+
+#[derive(Clone)]
+struct MockGrpcService {
+    state: Arc<MockState>,
+}
+
+impl MockGrpcService {
+    fn handle(&self, payload: Payload, remote_addr: Option<SocketAddr>) -> Payload {
+        let metadata = payload.metadata.unwrap_or_default();
+        let body = payload.body.unwrap_or_default().value;
+        if !self.state.check_token(
+            metadata
+                .headers
+                .get(ACCESS_TOKEN_HEADER)
+                .map(|e| e.as_str()),
+        ) {
+            return error_response(403, "invalid access token", None);
+        }
+        if metadata.r#type != "ServerCheckRequest"
+            && !self.state.check_access_key(&metadata.headers)
+        {
+            return error_response(403, "invalid signature", None);
+        }
+        let result = match metadata.r#type.as_str() {
+            "ServerCheckRequest" => Ok(build_response(
+                "ServerCheckResponse",
+                &ServerCheckResponse {
+                    result_code: SUCCESS_CODE,
+                    connection_id: remote_addr.map(|e| e.to_string()),
+                    ..Default::default()
+                },
+            )),
+            "ConfigQueryRequest" => decode(&body).map(|r| self.config_query(r)),
+            "ConfigPublishRequest" => decode(&body).map(|r| self.config_publish(r)),
+            "ConfigRemoveRequest" => decode(&body).map(|r: ConfigRemoveRequest| {
+                let key = ConfigKey::new(&r.data_id, &r.group, &r.tenant);
+                self.state.remove_config(&key);
+                base_response("ConfigRemoveResponse", r.request_id)
+            }),
+            "ConfigBatchListenRequest" => decode(&body).map(|r| self.config_listen(r, remote_addr)),
+            "InstanceRequest" | "PersistentInstanceRequest" => {
+                decode(&body).map(|r| self.instance(r, remote_addr))
+            }
+            "BatchInstanceRequest" => decode(&body).map(|r| self.batch_instance(r, remote_addr)),
+            "SubscribeServiceRequest" => decode(&body).map(|r| self.subscribe(r, remote_addr)),
+            "ServiceQueryRequest" => decode(&body).map(|r| self.service_query(r)),
+            t => Ok(error_response(
+                501,
+                &format!("unsupported request type:{}", t),
+                None,
+            )),
+        };
+        result.unwrap_or_else(|err| error_response(400, &err, None))
+    }
+
+    fn config_query(&self, request: ConfigQueryRequest) -> Payload {
+        let key = ConfigKey::new(&request.data_id, &request.group, &request.tenant);
+        match self.state.get_config(&key) {
+            Some(content) => build_response(
+                "ConfigQueryResponse",
+                &ConfigQueryResponse {
+                    result_code: SUCCESS_CODE,
+                    request_id: request.request_id,
+                    md5: Some(get_md5(&content)),
+                    content,
+                    last_modified: now_millis(),
+                    ..Default::default()
+                },
+            ),
+            None => build_response(
+                "ConfigQueryResponse",
+                &ConfigQueryResponse {
+                    result_code: ERROR_CODE,
+                    error_code: CONFIG_NOT_FOUND,
+                    message: Some("config data not exist".to_owned()),
+                    request_id: request.request_id,
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+
+    fn config_publish(&self, request: ConfigPublishRequest) -> Payload {
+        let key = ConfigKey::new(&request.data_id, &request.group, &request.tenant);
+        let cas_md5 = request.cas_md5.as_deref().filter(|e| !e.is_empty());
+        if self.state.publish_config(&key, &request.content, cas_md5) {
+            base_response("ConfigPublishResponse", request.request_id)
+        } else {
+            error_response(
+                ERROR_CODE,
+                "cas publish fail, server md5 may have changed.",
+                request.request_id,
+            )
+        }
+    }
+
+    fn config_listen(
+        &self,
+        request: ConfigBatchListenRequest,
+        remote_addr: Option<SocketAddr>,
+    ) -> Payload {
+        let mut items = vec![];
+        {
+            let mut store = self.state.store.lock().unwrap();
+            let mut conn = remote_addr.map(|addr| store.conns.entry(addr).or_default());
+            for context in request.config_listen_contexts {
+                let key = ConfigKey::new(&context.data_id, &context.group, &context.tenant);
+                if let Some(conn) = conn.as_mut() {
+                    if request.listen {
+                        conn.listen_configs
+                            .insert(normalize_config_key(&key), key.clone());
+                    } else {
+                        conn.listen_configs.remove(&normalize_config_key(&key));
+                    }
+                }
+                if request.listen {
+                    items.push((key, context.md5));
+                }
+            }
+        }
+        let changed_configs = self
+            .state
+            .changed_configs(&items)
+            .into_iter()
+            .map(|key| ConfigContext {
+                data_id: key.data_id,
+                group: key.group,
+                tenant: key.tenant,
+            })
+            .collect();
+        build_response(
+            "ConfigChangeBatchListenResponse",
+            &ConfigChangeBatchListenResponse {
+                result_code: SUCCESS_CODE,
+                request_id: request.request_id,
+                changed_configs,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn instance(&self, request: InstanceRequest, remote_addr: Option<SocketAddr>) -> Payload {
+        let key = service_key(
+            &request.namespace,
+            &request.group_name,
+            &request.service_name,
+        );
+        if let Some(instance) = request.instance {
+            let instance = to_instance(&key, instance);
+            if request.r#type.as_deref() == Some("deregisterInstance") {
+                self.state.remove_instance(key, &instance);
+            } else {
+                self.state.register_instance(key, instance, remote_addr);
+            }
+        }
+        build_response(
+            "InstanceResponse",
+            &InstanceResponse {
+                result_code: SUCCESS_CODE,
+                request_id: request.request_id,
+                r#type: request.r#type,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn batch_instance(
+        &self,
+        request: BatchInstanceRequest,
+        remote_addr: Option<SocketAddr>,
+    ) -> Payload {
+        let key = service_key(
+            &request.namespace,
+            &request.group_name,
+            &request.service_name,
+        );
+        for instance in request.instances.unwrap_or_default() {
+            let instance = to_instance(&key, instance);
+            self.state
+                .register_instance(key.clone(), instance, remote_addr);
+        }
+        build_response(
+            "BatchInstanceResponse",
+            &BatchInstanceResponse {
+                result_code: SUCCESS_CODE,
+                request_id: request.request_id,
+                r#type: request.r#type,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn subscribe(
+        &self,
+        request: SubscribeServiceRequest,
+        remote_addr: Option<SocketAddr>,
+    ) -> Payload {
+        let key = service_key(
+            &request.namespace,
+            &request.group_name,
+            &request.service_name,
+        );
+        let (instances, reach_protection_threshold) = {
+            let mut store = self.state.store.lock().unwrap();
+            if let Some(addr) = remote_addr {
+                let conn = store.conns.entry(addr).or_default();
+                if request.subscribe {
+                    conn.subscribes
+                        .insert(key.clone(), request.namespace.clone());
+                } else {
+                    conn.subscribes.remove(&key);
+                }
+            }
+            (
+                store.get_instances(&key),
+                store.reach_protection_threshold(&key),
+            )
+        };
+        build_response(
+            "SubscribeServiceResponse",
+            &SubscribeServiceResponse {
+                result_code: SUCCESS_CODE,
+                request_id: request.request_id,
+                service_info: Some(build_service_info(
+                    &key,
+                    instances,
+                    reach_protection_threshold,
+                )),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn service_query(&self, request: ServiceQueryRequest) -> Payload {
+        let key = service_key(
+            &request.namespace,
+            &request.group_name,
+            &request.service_name,
+        );
+        let clusters: HashSet<String> = request
+            .cluster
+            .unwrap_or_default()
+            .split(',')
+            .filter(|e| !e.is_empty())
+            .map(|e| e.to_owned())
+            .collect();
+        let (instances, reach_protection_threshold) =
+            self.state
+                .query_instances(&key, &clusters, request.healthy_only.unwrap_or(false));
+        build_response(
+            "ServiceQueryResponse",
+            &ServiceQueryResponse {
+                result_code: SUCCESS_CODE,
+                request_id: request.request_id,
+                service_info: Some(build_service_info(
+                    &key,
+                    instances,
+                    reach_protection_threshold,
+                )),
+                ..Default::default()
+            },
+        )
+    }
+}
+
+fn decode<T: DeserializeOwned>(body: &[u8]) -> Result<T, String> {
+    serde_json::from_slice(body).map_err(|err| format!("decode request error,{}", err))
+}
+
+#[tonic::async_trait]
+impl Request for MockGrpcService {
+    async fn request(&self, request: tonic::Request<Payload>) -> Result<Response<Payload>, Status> {
+        match self.state.take_failure().await {
+            Some(MockFailure::Disconnect) => {
+                return Err(Status::unavailable("mock disconnect"));
+            }
+            Some(MockFailure::Error(code)) => {
+                return Ok(Response::new(error_response(code, "mock failure", None)));
+            }
+            _ => {}
+        }
+        let remote_addr = request.remote_addr();
+        Ok(Response::new(
+            self.handle(request.into_inner(), remote_addr),
+        ))
+    }
+}
+
+#[tonic::async_trait]
+impl BiRequestStream for MockGrpcService {
+    type requestBiStreamStream = UnboundedReceiverStream<Result<Payload, Status>>;
+
+    async fn request_bi_stream(
+        &self,
+        request: tonic::Request<Streaming<Payload>>,
+    ) -> Result<Response<Self::requestBiStreamStream>, Status> {
+        let remote_addr = request.remote_addr();
+        let mut stream = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        if let Some(addr) = remote_addr {
+            let mut store = self.state.store.lock().unwrap();
+            store.conns.entry(addr).or_default().sender = Some(tx);
+        }
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            //客户端发来的 ConnectionSetupRequest 与推送响应不需要处理
+            while let Some(Ok(_)) = stream.next().await {}
+            if let Some(addr) = remote_addr {
+                state.store.lock().unwrap().remove_conn(&addr);
+            }
+        });
+        Ok(Response::new(UnboundedReceiverStream::new(rx)))
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::{MockFailure, MockState, ServiceKey};
+use crate::client::config_client::listener::ListenerItem;
+use crate::client::config_client::ConfigKey;
+use crate::client::naming_client::{BeatInfo, Instance, NamingUtils};
+use crate::client::now_millis;
+
+//长轮询的最大等待时间
+const MAX_LISTEN_TIMEOUT: u64 = 30000;
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    //query 与 form body 合并后的参数
+    params: HashMap<String, String>,
+    //url 中的参数名
+    query_names: HashSet<String>,
+}
+
+impl HttpRequest {
+    fn param(&self, name: &str) -> &str {
+        self.params
+            .get(name)
+            .map(|e| e.as_str())
+            .unwrap_or_default()
+    }
+
+    /// 依次读取请求头 accessToken、Authorization 与参数中的 accessToken
+    fn access_token(&self, header_only: bool) -> Option<&str> {
+        if let Some(token) = self.headers.get("accesstoken") {
+            return Some(token);
+        }
+        if let Some(token) = self
+            .headers
+            .get("authorization")
+            .and_then(|e| e.strip_prefix("Bearer "))
+        {
+            return Some(token);
+        }
+        if header_only {
+            return None;
+        }
+        self.params.get("accessToken").map(|e| e.as_str())
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    body: String,
+}
+
+impl HttpResponse {
+    fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+
+    fn ok(body: impl Into<String>) -> Self {
+        Self::new(200, body)
+    }
+}
+
+pub(crate) async fn serve(listener: TcpListener, state: Arc<MockState>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(handle_conn(stream, state.clone()));
+    }
+}
+
+async fn handle_conn(mut stream: TcpStream, state: Arc<MockState>) {
+    let mut buf = Vec::new();
+    loop {
+        let request = match read_request(&mut stream, &mut buf).await {
+            Some(request) => request,
+            None => return,
+        };
+        let response = match state.take_failure().await {
+            Some(MockFailure::Disconnect) => return,
+            Some(MockFailure::Error(status)) => HttpResponse::new(status, "mock failure"),
+            _ => handle_request(&request, &state).await,
+        };
+        if write_response(&mut stream, response).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn find_header_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|e| e == b"\r\n\r\n")
+}
+
+async fn read_request(stream: &mut TcpStream, buf: &mut Vec<u8>) -> Option<HttpRequest> {
+    let mut tmp = [0u8; 4096];
+    let header_end = loop {
+        if let Some(pos) = find_header_end(buf) {
+            break pos;
+        }
+        let n = stream.read(&mut tmp).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&tmp[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_owned();
+    let uri = request_line.next()?.to_owned();
+    let mut headers = HashMap::new();
+    for line in lines {
+        if let Some((k, v)) = line.split_once(':') {
+            headers.insert(k.trim().to_ascii_lowercase(), v.trim().to_owned());
+        }
+    }
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|e| e.parse().ok())
+        .unwrap_or_default();
+    let body_start = header_end + 4;
+    while buf.len() < body_start + content_length {
+        let n = stream.read(&mut tmp).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&tmp[..n]);
+    }
+    let body = buf[body_start..body_start + content_length].to_vec();
+    buf.drain(..body_start + content_length);
+
+    let (path, query) = uri.split_once('?').unwrap_or((&uri, ""));
+    let mut params: HashMap<String, String> = serde_urlencoded::from_str(query).unwrap_or_default();
+    let query_names = params.keys().cloned().collect();
+    let form: HashMap<String, String> = serde_urlencoded::from_bytes(&body).unwrap_or_default();
+    params.extend(form);
+    Some(HttpRequest {
+        method,
+        path: path.to_owned(),
+        headers,
+        params,
+        query_names,
+    })
+}
+
+async fn write_response(stream: &mut TcpStream, response: HttpResponse) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} MOCK\r\nContent-Type: text/plain;charset=UTF-8\r\nContent-Length: {}\r\n\r\n",
+        response.status,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.flush().await
+}
+
+async fn handle_request(request: &HttpRequest, state: &MockState) -> HttpResponse {
+    let path = request.path.as_str();
+    if path == "/nacos/v1/auth/login" {
+        return login(request, state);
+    }
+    if path == "/nacos/serverlist" {
+        return HttpResponse::ok(state.server_list());
+    }
+    if !state.check_token(request.access_token(state.header_token_only())) {
+        return HttpResponse::new(403, "invalid access token");
+    }
+    if !state.check_access_key(&request.headers) {
+        return HttpResponse::new(403, "invalid signature");
+    }
+    match (request.method.as_str(), path) {
+        ("GET", "/nacos/v1/cs/configs") => match state.get_config(&config_key(request)) {
+            Some(content) => HttpResponse::ok(content),
+            None => HttpResponse::new(404, "config data not exist"),
+        },
+        ("POST", "/nacos/v1/cs/configs") => {
+            let cas_md5 = request.params.get("casMd5").map(|e| e.as_str());
+            if state.publish_config(&config_key(request), request.param("content"), cas_md5) {
+                HttpResponse::ok("true")
+            } else {
+                HttpResponse::new(409, "cas publish fail, server md5 may have changed.")
+            }
+        }
+        ("DELETE", "/nacos/v1/cs/configs") => {
+            state.remove_config(&config_key(request));
+            HttpResponse::ok("true")
+        }
+        ("POST", "/nacos/v1/cs/configs/listener") => listen_configs(request, state).await,
+        ("POST", "/nacos/v1/ns/instance") | ("PUT", "/nacos/v1/ns/instance") => {
+            let (key, instance) = web_instance(request);
+            state.register_instance(key, instance, None);
+            HttpResponse::ok("ok")
+        }
+        ("DELETE", "/nacos/v1/ns/instance") => {
+            let (key, instance) = web_instance(request);
+            state.remove_instance(key, &instance);
+            HttpResponse::ok("ok")
+        }
+        ("PUT", "/nacos/v1/ns/instance/beat") => beat(request, state),
+        ("GET", "/nacos/v1/ns/instance/list") => instance_list(request, state),
+        _ => HttpResponse::new(404, "not found"),
+    }
+}
+
+fn login(request: &HttpRequest, state: &MockState) -> HttpResponse {
+    if state.header_token_only()
+        && (request.query_names.contains("username") || request.query_names.contains("password"))
+    {
+        return HttpResponse::new(403, "credentials in url are not allowed");
+    }
+    let access_token = match state.login(request.param("username"), request.param("password")) {
+        Some(v) => v,
+        None => return HttpResponse::new(403, "unknown user!"),
+    };
+    let token = serde_json::json!({
+        "accessToken": access_token,
+        "tokenTtl": 18000,
+        "globalAdmin": false,
+    });
+    HttpResponse::ok(token.to_string())
+}
+
+fn config_key(request: &HttpRequest) -> ConfigKey {
+    ConfigKey::new(
+        request.param("dataId"),
+        request.param("group"),
+        request.param("tenant"),
+    )
+}
+
+async fn listen_configs(request: &HttpRequest, state: &MockState) -> HttpResponse {
+    let items: Vec<(ConfigKey, String)> =
+        ListenerItem::decode_listener_items(request.param("Listening-Configs"))
+            .into_iter()
+            .map(|e| (e.key, e.md5))
+            .collect();
+    let timeout = request
+        .headers
+        .get("long-pulling-timeout")
+        .and_then(|e| e.parse().ok())
+        .unwrap_or(MAX_LISTEN_TIMEOUT)
+        .min(MAX_LISTEN_TIMEOUT);
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout);
+    loop {
+        let notified = state.config_notify.notified();
+        let changed = state.changed_configs(&items);
+        if !changed.is_empty() {
+            let mut text = String::new();
+            for key in changed {
+                text += &format!("{}\x02{}\x02{}\x01", key.data_id, key.group, key.tenant);
+            }
+            let body = serde_urlencoded::to_string([("v", text)]).unwrap();
+            return HttpResponse::ok(body.trim_start_matches("v="));
+        }
+        if tokio::time::timeout_at(deadline, notified).await.is_err() {
+            return HttpResponse::ok("");
+        }
+    }
+}
+
+fn split_service_name(request: &HttpRequest) -> (String, String) {
+    let service_name = request.param("serviceName");
+    match NamingUtils::split_group_and_serivce_name(service_name) {
+        Some((group_name, service_name)) => (group_name, service_name),
+        None => (
+            request.param("groupName").to_owned(),
+            service_name.to_owned(),
+        ),
+    }
+}
+
+fn web_instance(request: &HttpRequest) -> (ServiceKey, Instance) {
+    let (group_name, service_name) = split_service_name(request);
+    let mut instance = Instance::new(
+        request.param("ip"),
+        request.param("port").parse().unwrap_or_default(),
+        &service_name,
+        &group_name,
+        request.param("clusterName"),
+        request.param("namespaceId"),
+        serde_json::from_str(request.param("metadata")).ok(),
+    );
+    instance.weight = request.param("weight").parse().unwrap_or(1f32);
+    instance.enabled = request.param("enabled") != "false";
+    instance.healthy = request.param("healthy") != "false";
+    instance.ephemeral = request.param("ephemeral") != "false";
+    let key = ServiceKey::new(
+        &instance.namespace_id,
+        &instance.group_name,
+        &instance.service_name,
+    );
+    (key, instance)
+}
+
+fn beat(request: &HttpRequest, state: &MockState) -> HttpResponse {
+    let beat: BeatInfo = match serde_json::from_str(request.param("beat")) {
+        Ok(beat) => beat,
+        Err(_) => return HttpResponse::new(400, "invalid beat"),
+    };
+    let (group_name, service_name) = split_service_name(request);
+    let key = ServiceKey::new(request.param("namespaceId"), &group_name, &service_name);
+    let exists = state
+        .store
+        .lock()
+        .unwrap()
+        .get_instances(&key)
+        .iter()
+        .any(|e| e.ip == beat.ip && e.port == beat.port);
+    if !exists {
+        //与服务端一致,心跳时实例不存在则重新注册
+        let mut instance = Instance::new(
+            &beat.ip,
+            beat.port,
+            &service_name,
+            &group_name,
+            &beat.cluster,
+            request.param("namespaceId"),
+            Some(beat.metadata),
+        );
+        instance.weight = beat.weight;
+        state.register_instance(key, instance, None);
+    }
+    HttpResponse::ok("ok")
+}
+
+fn instance_list(request: &HttpRequest, state: &MockState) -> HttpResponse {
+    let (group_name, service_name) = split_service_name(request);
+    let key = ServiceKey::new(request.param("namespaceId"), &group_name, &service_name);
+    let clusters: HashSet<String> = request
+        .param("clusters")
+        .split(',')
+        .filter(|e| !e.is_empty())
+        .map(|e| e.to_owned())
+        .collect();
+    let healthy_only = request.param("healthyOnly") == "true";
+    let grouped_name = NamingUtils::get_group_and_service_name(&service_name, &group_name);
+    let (instances, _) = state.query_instances(&key, &clusters, healthy_only);
+    let hosts: Vec<serde_json::Value> = instances
+        .into_iter()
+        .map(|e| {
+            serde_json::json!({
+                "instanceId": format!("{}#{}#{}#{}", e.ip, e.port, e.cluster_name, grouped_name),
+                "ip": e.ip,
+                "port": e.port,
+                "weight": e.weight,
+                "healthy": e.healthy,
+                "enabled": e.enabled,
+                "ephemeral": e.ephemeral,
+                "clusterName": e.cluster_name,
+                "serviceName": grouped_name,
+                "metadata": e.metadata.unwrap_or_default(),
+            })
+        })
+        .collect();
+    let result = serde_json::json!({
+        "name": grouped_name,
+        "clusters": request.param("clusters"),
+        "cacheMillis": 10000,
+        "hosts": hosts,
+        "lastRefTime": now_millis(),
+        "checksum": "",
+        "protectThreshold": state.protect_threshold(&key),
+    });
+    HttpResponse::ok(result.to_string())
+}
@@ -0,0 +1,616 @@
+//! 测试用的内存 nacos 服务,需要开启 `testkit` feature。
+//!
+//! 在本地随机端口上同时提供 v1 http 接口、gRPC 接口与地址服务器接口,
+//! 支持推送配置变更、推送服务实例变更与注入故障。
+//!
+//! ```ignore
+//! let server = MockNacosServer::start()?;
+//! let config_client = ClientBuilder::new()
+//!     .set_endpoint_addrs(&server.addr())
+//!     .build_config_client();
+//! server.publish_config(&ConfigKey::new("foo", "DEFAULT_GROUP", "public"), "bar");
+//! ```
+
+mod grpc;
+mod http;
+
+use std::collections::{HashMap, HashSet};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+use crate::client::config_client::ConfigKey;
+use crate::client::naming_client::{Instance, ServiceInstanceKey};
+use crate::client::{get_md5, AccessKeyAuthProvider, HostInfo};
+use crate::grpc::api_model::{
+    ConfigChangeNotifyRequest, ConnectResetRequest, NotifySubscriberRequest,
+};
+use crate::grpc::nacos_proto::Payload;
+use crate::grpc::utils::PayloadUtils;
+
+type PushSender = tokio::sync::mpsc::UnboundedSender<Result<Payload, tonic::Status>>;
+
+/// 注入的故障
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockFailure {
+    /// http 接口返回该状态码,gRPC 接口返回该 errorCode
+    Error(u16),
+    /// 延迟后再正常处理
+    Delay(Duration),
+    /// http 接口直接关闭连接,gRPC 接口返回 unavailable
+    Disconnect,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ServiceKey {
+    namespace_id: String,
+    group_name: String,
+    service_name: String,
+}
+
+impl ServiceKey {
+    fn new(namespace_id: &str, group_name: &str, service_name: &str) -> Self {
+        Self {
+            namespace_id: normalize_namespace(namespace_id),
+            group_name: if group_name.is_empty() {
+                "DEFAULT_GROUP".to_owned()
+            } else {
+                group_name.to_owned()
+            },
+            service_name: service_name.to_owned(),
+        }
+    }
+}
+
+fn normalize_namespace(namespace_id: &str) -> String {
+    if namespace_id.is_empty() {
+        "public".to_owned()
+    } else {
+        namespace_id.to_owned()
+    }
+}
+
+fn normalize_config_key(key: &ConfigKey) -> ConfigKey {
+    ConfigKey::new(&key.data_id, &key.group, &normalize_namespace(&key.tenant))
+}
+
+struct MockInstance {
+    instance: Instance,
+    //注册该实例的 gRPC 连接,连接断开时删除临时实例
+    owner: Option<SocketAddr>,
+}
+
+#[derive(Default)]
+struct GrpcConn {
+    sender: Option<PushSender>,
+    //normalized key -> 客户端监听时使用的 key
+    listen_configs: HashMap<ConfigKey, ConfigKey>,
+    //service key -> 客户端订阅时使用的 namespace
+    subscribes: HashMap<ServiceKey, Option<String>>,
+}
+
+#[derive(Default)]
+struct MockStore {
+    configs: HashMap<ConfigKey, String>,
+    services: HashMap<ServiceKey, Vec<MockInstance>>,
+    conns: HashMap<SocketAddr, GrpcConn>,
+    credentials: Option<(String, String)>,
+    access_key: Option<(String, String)>,
+    protect_thresholds: HashMap<ServiceKey, f32>,
+    //调用 expire_tokens 后递增,之前签发的 token 失效
+    token_version: u64,
+    //http 请求只接受请求头中的 token 与 body 中的登录参数
+    header_token_only: bool,
+    //地址服务器返回的地址列表
+    server_list: Vec<String>,
+    failure: Option<(MockFailure, Option<usize>)>,
+    push_id: u64,
+}
+
+impl MockStore {
+    fn access_token(&self) -> String {
+        format!("{}-{}", MOCK_TOKEN, self.token_version)
+    }
+
+    fn protect_threshold(&self, key: &ServiceKey) -> f32 {
+        self.protect_thresholds
+            .get(key)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// 与服务端一致:健康实例占比低于保护阈值
+    fn reach_protection_threshold(&self, key: &ServiceKey) -> bool {
+        let threshold = self.protect_threshold(key);
+        let instances = self.get_instances(key);
+        if threshold <= 0f32 || instances.is_empty() {
+            return false;
+        }
+        let healthy = instances.iter().filter(|e| e.healthy && e.enabled).count();
+        (healthy as f32) / (instances.len() as f32) < threshold
+    }
+
+    fn next_push_id(&mut self) -> String {
+        self.push_id += 1;
+        self.push_id.to_string()
+    }
+
+    fn get_instances(&self, key: &ServiceKey) -> Vec<Instance> {
+        self.services
+            .get(key)
+            .map(|list| list.iter().map(|e| e.instance.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    fn upsert_instance(&mut self, key: ServiceKey, instance: Instance, owner: Option<SocketAddr>) {
+        let list = self.services.entry(key).or_default();
+        let item = MockInstance { instance, owner };
+        match list
+            .iter_mut()
+            .find(|e| same_instance(&e.instance, &item.instance))
+        {
+            Some(old) => *old = item,
+            None => list.push(item),
+        }
+    }
+
+    fn remove_instance(&mut self, key: &ServiceKey, instance: &Instance) -> bool {
+        if let Some(list) = self.services.get_mut(key) {
+            let len = list.len();
+            list.retain(|e| !same_instance(&e.instance, instance));
+            return len != list.len();
+        }
+        false
+    }
+
+    fn notify_config_change(&mut self, key: &ConfigKey) {
+        let mut pushes = vec![];
+        for conn in self.conns.values() {
+            if let (Some(sender), Some(listen_key)) = (&conn.sender, conn.listen_configs.get(key)) {
+                pushes.push((sender.clone(), listen_key.clone()));
+            }
+        }
+        for (sender, listen_key) in pushes {
+            let request = ConfigChangeNotifyRequest {
+                request_id: Some(self.next_push_id()),
+                data_id: listen_key.data_id,
+                group: listen_key.group,
+                tenant: Some(listen_key.tenant),
+                ..Default::default()
+            };
+            let payload = PayloadUtils::build_payload(
+                "ConfigChangeNotifyRequest",
+                serde_json::to_string(&request).unwrap(),
+            );
+            sender.send(Ok(payload)).ok();
+        }
+    }
+
+    fn notify_service_change(&mut self, key: &ServiceKey) {
+        let mut pushes = vec![];
+        for conn in self.conns.values() {
+            if let (Some(sender), Some(namespace)) = (&conn.sender, conn.subscribes.get(key)) {
+                pushes.push((sender.clone(), namespace.clone()));
+            }
+        }
+        for (sender, namespace) in pushes {
+            let request = NotifySubscriberRequest {
+                request_id: Some(self.next_push_id()),
+                namespace,
+                service_name: Some(key.service_name.clone()),
+                group_name: Some(key.group_name.clone()),
+                service_info: Some(grpc::build_service_info(
+                    key,
+                    self.get_instances(key),
+                    self.reach_protection_threshold(key),
+                )),
+                ..Default::default()
+            };
+            let payload = PayloadUtils::build_payload(
+                "NotifySubscriberRequest",
+                serde_json::to_string(&request).unwrap(),
+            );
+            sender.send(Ok(payload)).ok();
+        }
+    }
+
+    /// gRPC 连接断开,删除连接注册的临时实例
+    fn remove_conn(&mut self, addr: &SocketAddr) {
+        self.conns.remove(addr);
+        let mut changed = vec![];
+        for (key, list) in self.services.iter_mut() {
+            let len = list.len();
+            list.retain(|e| !(e.instance.ephemeral && e.owner.as_ref() == Some(addr)));
+            if len != list.len() {
+                changed.push(key.clone());
+            }
+        }
+        for key in changed {
+            self.notify_service_change(&key);
+        }
+    }
+}
+
+fn same_instance(a: &Instance, b: &Instance) -> bool {
+    a.ip == b.ip && a.port == b.port && a.cluster_name == b.cluster_name
+}
+
+pub(crate) struct MockState {
+    store: Mutex<MockStore>,
+    //http 长轮询监听在配置变更时唤醒
+    config_notify: Notify,
+}
+
+const MOCK_TOKEN: &str = "mock-access-token";
+
+impl MockState {
+    /// 取出一次待注入的故障
+    async fn take_failure(&self) -> Option<MockFailure> {
+        let failure = {
+            let mut store = self.store.lock().unwrap();
+            let (failure, times) = store.failure.as_mut()?;
+            let failure = failure.clone();
+            if let Some(times) = times {
+                *times -= 1;
+                if *times == 0 {
+                    store.failure = None;
+                }
+            }
+            failure
+        };
+        match failure {
+            MockFailure::Delay(duration) => {
+                tokio::time::sleep(duration).await;
+                None
+            }
+            _ => Some(failure),
+        }
+    }
+
+    fn header_token_only(&self) -> bool {
+        self.store.lock().unwrap().header_token_only
+    }
+
+    fn check_token(&self, token: Option<&str>) -> bool {
+        let store = self.store.lock().unwrap();
+        store.credentials.is_none() || token == Some(store.access_token().as_str())
+    }
+
+    /// 开启 AccessKey 鉴权时校验签名头部;header 名称不区分大小写
+    fn check_access_key(&self, headers: &HashMap<String, String>) -> bool {
+        let store = self.store.lock().unwrap();
+        let (access_key, secret_key) = match &store.access_key {
+            Some(v) => v,
+            None => return true,
+        };
+        let get = |name: &str| {
+            headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        };
+        let provider = AccessKeyAuthProvider::new(access_key, secret_key);
+        if let (Some(ak), Some(data), Some(signature)) = (get("ak"), get("data"), get("signature"))
+        {
+            return ak == access_key && provider.sign(data) == signature;
+        }
+        get("Spas-AccessKey") == Some(access_key.as_str())
+            && get("Timestamp").is_some()
+            && get("Spas-Signature").is_some()
+    }
+
+    fn server_list(&self) -> String {
+        self.store.lock().unwrap().server_list.join("\n")
+    }
+
+    /// 登录成功时返回当前有效的 token
+    fn login(&self, username: &str, password: &str) -> Option<String> {
+        let store = self.store.lock().unwrap();
+        match &store.credentials {
+            Some((u, p)) if u != username || p != password => None,
+            _ => Some(store.access_token()),
+        }
+    }
+
+    fn get_config(&self, key: &ConfigKey) -> Option<String> {
+        let store = self.store.lock().unwrap();
+        store.configs.get(&normalize_config_key(key)).cloned()
+    }
+
+    fn publish_config(&self, key: &ConfigKey, content: &str, cas_md5: Option<&str>) -> bool {
+        let key = normalize_config_key(key);
+        let mut store = self.store.lock().unwrap();
+        if let Some(cas_md5) = cas_md5 {
+            let md5 = store
+                .configs
+                .get(&key)
+                .map(|e| get_md5(e))
+                .unwrap_or_default();
+            if md5 != cas_md5 {
+                return false;
+            }
+        }
+        store.configs.insert(key.clone(), content.to_owned());
+        store.notify_config_change(&key);
+        drop(store);
+        self.config_notify.notify_waiters();
+        true
+    }
+
+    fn remove_config(&self, key: &ConfigKey) {
+        let key = normalize_config_key(key);
+        let mut store = self.store.lock().unwrap();
+        if store.configs.remove(&key).is_some() {
+            store.notify_config_change(&key);
+        }
+        drop(store);
+        self.config_notify.notify_waiters();
+    }
+
+    /// 返回 md5 与服务端不一致的配置
+    fn changed_configs(&self, items: &[(ConfigKey, String)]) -> Vec<ConfigKey> {
+        let store = self.store.lock().unwrap();
+        items
+            .iter()
+            .filter(|(key, md5)| {
+                let server_md5 = store
+                    .configs
+                    .get(&normalize_config_key(key))
+                    .map(|e| get_md5(e))
+                    .unwrap_or_default();
+                &server_md5 != md5
+            })
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn query_instances(
+        &self,
+        key: &ServiceKey,
+        clusters: &HashSet<String>,
+        healthy_only: bool,
+    ) -> (Vec<Instance>, bool) {
+        let store = self.store.lock().unwrap();
+        //达到保护阈值时返回全部实例
+        let reach_protection_threshold = store.reach_protection_threshold(key);
+        let healthy_only = healthy_only && !reach_protection_threshold;
+        let instances = store
+            .get_instances(key)
+            .into_iter()
+            .filter(|e| clusters.is_empty() || clusters.contains(&e.cluster_name))
+            .filter(|e| !healthy_only || (e.healthy && e.enabled))
+            .collect();
+        (instances, reach_protection_threshold)
+    }
+
+    fn protect_threshold(&self, key: &ServiceKey) -> f32 {
+        self.store.lock().unwrap().protect_threshold(key)
+    }
+
+    fn register_instance(&self, key: ServiceKey, instance: Instance, owner: Option<SocketAddr>) {
+        let mut store = self.store.lock().unwrap();
+        store.upsert_instance(key.clone(), instance, owner);
+        store.notify_service_change(&key);
+    }
+
+    fn remove_instance(&self, key: ServiceKey, instance: &Instance) {
+        let mut store = self.store.lock().unwrap();
+        if store.remove_instance(&key, instance) {
+            store.notify_service_change(&key);
+        }
+    }
+}
+
+/// 内存 nacos 服务;drop 时停止服务
+pub struct MockNacosServer {
+    state: Arc<MockState>,
+    http_port: u16,
+    grpc_port: u16,
+    shutdown_tx: Option<tokio::sync::oneshot::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl MockNacosServer {
+    /// 在 127.0.0.1 的随机端口上启动服务,服务运行在独立线程中
+    pub fn start() -> anyhow::Result<Self> {
+        let http_listener = TcpListener::bind("127.0.0.1:0")?;
+        let grpc_listener = TcpListener::bind("127.0.0.1:0")?;
+        http_listener.set_nonblocking(true)?;
+        grpc_listener.set_nonblocking(true)?;
+        let http_port = http_listener.local_addr()?.port();
+        let grpc_port = grpc_listener.local_addr()?.port();
+        let store = MockStore {
+            server_list: vec![format!("127.0.0.1:{}#{}", http_port, grpc_port)],
+            ..Default::default()
+        };
+        let state = Arc::new(MockState {
+            store: Mutex::new(store),
+            config_notify: Notify::new(),
+        });
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server_state = state.clone();
+        let thread = std::thread::Builder::new()
+            .name("nacos-mock-server".to_owned())
+            .spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("build mock server runtime error");
+                rt.block_on(async move {
+                    let http_listener = tokio::net::TcpListener::from_std(http_listener)
+                        .expect("mock http listener error");
+                    let grpc_listener = tokio::net::TcpListener::from_std(grpc_listener)
+                        .expect("mock grpc listener error");
+                    tokio::spawn(http::serve(http_listener, server_state.clone()));
+                    tokio::spawn(grpc::serve(grpc_listener, server_state));
+                    shutdown_rx.await.ok();
+                });
+            })?;
+        Ok(Self {
+            state,
+            http_port,
+            grpc_port,
+            shutdown_tx: Some(shutdown_tx),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn http_port(&self) -> u16 {
+        self.http_port
+    }
+
+    pub fn grpc_port(&self) -> u16 {
+        self.grpc_port
+    }
+
+    /// 服务地址,可直接用于 `ClientBuilder::set_endpoint_addrs`
+    pub fn addr(&self) -> String {
+        format!("127.0.0.1:{}#{}", self.http_port, self.grpc_port)
+    }
+
+    pub fn host(&self) -> HostInfo {
+        HostInfo::new_with_grpc("127.0.0.1", self.http_port as u32, self.grpc_port as u32)
+    }
+
+    /// 地址服务器地址,可用于 `AddressServer::new`
+    pub fn address_server_url(&self) -> String {
+        format!("http://127.0.0.1:{}/nacos/serverlist", self.http_port)
+    }
+
+    /// 修改地址服务器返回的地址列表,默认只包含当前服务
+    pub fn set_server_list(&self, server_list: Vec<String>) {
+        self.state.store.lock().unwrap().server_list = server_list;
+    }
+
+    /// 开启鉴权;之后的请求都需要先使用该用户名密码登录
+    pub fn set_credentials(&self, username: &str, password: &str) {
+        let mut store = self.state.store.lock().unwrap();
+        store.credentials = Some((username.to_owned(), password.to_owned()));
+    }
+
+    /// http 请求只接受请求头中的 token,url 中携带 token 或用户名密码时返回 403
+    pub fn require_header_token(&self) {
+        self.state.store.lock().unwrap().header_token_only = true;
+    }
+
+    /// 使已签发的 token 全部失效,模拟服务端 token 过期
+    pub fn expire_tokens(&self) {
+        self.state.store.lock().unwrap().token_version += 1;
+    }
+
+    /// 开启 AccessKey 签名鉴权;之后的请求都需要携带签名头部
+    pub fn set_access_key(&self, access_key: &str, secret_key: &str) {
+        let mut store = self.state.store.lock().unwrap();
+        store.access_key = Some((access_key.to_owned(), secret_key.to_owned()));
+    }
+
+    /// 发布配置并通知监听的客户端
+    pub fn publish_config(&self, key: &ConfigKey, content: &str) {
+        self.state.publish_config(key, content, None);
+    }
+
+    /// 删除配置并通知监听的客户端
+    pub fn remove_config(&self, key: &ConfigKey) {
+        self.state.remove_config(key);
+    }
+
+    pub fn get_config(&self, key: &ConfigKey) -> Option<String> {
+        self.state.get_config(key)
+    }
+
+    /// 覆盖服务的全部实例并推送给订阅的客户端
+    pub fn set_instances(&self, key: &ServiceInstanceKey, instances: Vec<Instance>) {
+        let key = ServiceKey::new(
+            key.namespace_id.as_deref().unwrap_or_default(),
+            &key.group_name,
+            &key.service_name,
+        );
+        let mut store = self.state.store.lock().unwrap();
+        let list = instances
+            .into_iter()
+            .map(|instance| MockInstance {
+                instance,
+                owner: None,
+            })
+            .collect();
+        store.services.insert(key.clone(), list);
+        store.notify_service_change(&key);
+    }
+
+    /// 设置服务的保护阈值;健康实例占比低于阈值时,查询健康实例也返回全部实例
+    pub fn set_protect_threshold(&self, key: &ServiceInstanceKey, threshold: f32) {
+        let key = ServiceKey::new(
+            key.namespace_id.as_deref().unwrap_or_default(),
+            &key.group_name,
+            &key.service_name,
+        );
+        let mut store = self.state.store.lock().unwrap();
+        store.protect_thresholds.insert(key.clone(), threshold);
+        store.notify_service_change(&key);
+    }
+
+    /// 服务当前的全部实例,包含客户端注册的实例
+    pub fn get_instances(&self, key: &ServiceInstanceKey) -> Vec<Instance> {
+        let key = ServiceKey::new(
+            key.namespace_id.as_deref().unwrap_or_default(),
+            &key.group_name,
+            &key.service_name,
+        );
+        self.state.store.lock().unwrap().get_instances(&key)
+    }
+
+    /// 注入故障;times 为 None 时一直生效,直到调用 `clear_failure`
+    pub fn inject_failure(&self, failure: MockFailure, times: Option<usize>) {
+        let mut store = self.state.store.lock().unwrap();
+        store.failure = match times {
+            Some(0) => None,
+            _ => Some((failure, times)),
+        };
+    }
+
+    pub fn clear_failure(&self) {
+        self.state.store.lock().unwrap().failure = None;
+    }
+
+    /// 向全部 gRPC 连接推送 ConnectResetRequest;target 为建议客户端切换的服务地址
+    pub fn reset_connections(&self, target: Option<&HostInfo>) {
+        let mut store = self.state.store.lock().unwrap();
+        let senders: Vec<PushSender> = store
+            .conns
+            .values()
+            .filter_map(|e| e.sender.clone())
+            .collect();
+        for sender in senders {
+            let request = ConnectResetRequest {
+                request_id: Some(store.next_push_id()),
+                server_ip: target.map(|e| e.ip.clone()),
+                server_port: target.map(|e| e.port.to_string()),
+                ..Default::default()
+            };
+            let payload = PayloadUtils::build_payload(
+                "ConnectResetRequest",
+                serde_json::to_string(&request).unwrap(),
+            );
+            sender.send(Ok(payload)).ok();
+        }
+    }
+
+    /// 当前建立了双向流的 gRPC 连接数
+    pub fn connection_count(&self) -> usize {
+        let store = self.state.store.lock().unwrap();
+        store.conns.values().filter(|e| e.sender.is_some()).count()
+    }
+}
+
+impl Drop for MockNacosServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown_tx.take() {
+            tx.send(()).ok();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
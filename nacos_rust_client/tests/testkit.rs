@@ -0,0 +1,689 @@
+#![cfg(feature = "testkit")]
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use nacos_rust_client::client::config_client::{ConfigKey, ConfigPublishResult};
+use nacos_rust_client::client::naming_client::{
+    Instance, NamingProtectPolicy, QueryInstanceListParams, ServiceInstanceKey,
+};
+use nacos_rust_client::client::{
+    AccessKeyAuthProvider, AccessTokenPosition, AddressServer, AuthInfo, AuthState, ClientBuilder,
+    ConfigClient, ConnectionPolicy, FileAuthProvider, NamingClient, RequestOperation,
+};
+use nacos_rust_client::testkit::{MockFailure, MockNacosServer};
+use tokio_stream::StreamExt;
+
+/// 分别使用 gRPC 与 HTTP 协议执行同一个检查
+macro_rules! protocol_test {
+    ($check:ident) => {
+        mod $check {
+            #[test]
+            fn grpc() {
+                super::$check(true);
+            }
+
+            #[test]
+            fn http() {
+                super::$check(false);
+            }
+        }
+    };
+}
+
+fn build_clients(
+    server: &MockNacosServer,
+    use_grpc: bool,
+) -> (Arc<ConfigClient>, Arc<NamingClient>) {
+    ClientBuilder::new()
+        .set_endpoint_addrs(&server.addr())
+        .set_use_grpc(use_grpc)
+        .build()
+}
+
+async fn wait_config(client: &ConfigClient, key: &ConfigKey, value: &str) -> bool {
+    for _ in 0..50 {
+        if let Ok(v) = client.get_config(key).await {
+            if v == value {
+                return true;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+async fn wait_instance(client: &NamingClient, service_name: &str, port: u32) -> bool {
+    for _ in 0..50 {
+        let params = QueryInstanceListParams::new_simple(service_name, "");
+        if let Ok(instance) = client.select_instance(params).await {
+            if instance.port == port {
+                return true;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+fn check_clients(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    let (config_client, naming_client) = build_clients(&server, use_grpc);
+    let service_name = format!("testkit_service_{}", use_grpc);
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("testkit_data", "");
+        let err = config_client.get_config(&key).await.unwrap_err();
+        assert!(err.is_not_found());
+
+        config_client.set_config(&key, "v1").await.unwrap();
+        assert!(wait_config(&config_client, &key, "v1").await);
+        assert_eq!(server.get_config(&key).as_deref(), Some("v1"));
+
+        server.publish_config(&key, "v2");
+        assert!(wait_config(&config_client, &key, "v2").await);
+
+        server.inject_failure(MockFailure::Error(500), Some(1));
+        assert!(config_client.get_config(&key).await.is_err());
+        assert!(wait_config(&config_client, &key, "v2").await);
+
+        naming_client
+            .register_async(Instance::new_simple("127.0.0.1", 8080, &service_name, ""))
+            .await
+            .unwrap();
+        assert!(wait_instance(&naming_client, &service_name, 8080).await);
+
+        config_client.shutdown().await.ok();
+        naming_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_clients);
+
+async fn check_build_async(server: &MockNacosServer) {
+    let (config_client, naming_client) = ClientBuilder::new()
+        .set_endpoint_addrs(&server.addr())
+        .build_async()
+        .await
+        .unwrap();
+    let key = config_client.gene_config_key("build_async_data", "");
+    config_client.set_config(&key, "v1").await.unwrap();
+    assert!(wait_config(&config_client, &key, "v1").await);
+
+    naming_client
+        .register_async(Instance::new_simple(
+            "127.0.0.1",
+            8080,
+            "build_async_service",
+            "",
+        ))
+        .await
+        .unwrap();
+    assert!(wait_instance(&naming_client, "build_async_service", 8080).await);
+
+    config_client.shutdown().await.ok();
+    naming_client.shutdown().await.ok();
+}
+
+#[tokio::test]
+async fn test_build_async_outside_actix_system() {
+    let server = MockNacosServer::start().unwrap();
+    check_build_async(&server).await;
+}
+
+#[test]
+fn test_build_async_in_actix_system() {
+    let server = MockNacosServer::start().unwrap();
+    actix::System::new().block_on(async move {
+        check_build_async(&server).await;
+    });
+}
+
+fn check_update_instance(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    let (_, naming_client) = build_clients(&server, use_grpc);
+    let service_name = format!("update_service_{}", use_grpc);
+    let key = ServiceInstanceKey::new(&service_name, "DEFAULT_GROUP");
+    actix::System::new().block_on(async move {
+        let mut instance = Instance::new_simple("127.0.0.1", 8080, &service_name, "DEFAULT_GROUP");
+        naming_client
+            .register_async(instance.clone())
+            .await
+            .unwrap();
+
+        instance.weight = 2f32;
+        naming_client
+            .update_instance(instance.clone())
+            .await
+            .unwrap();
+        assert_eq!(server.get_instances(&key)[0].weight, 2f32);
+
+        //未注册的实例不会被更新或注册
+        let unknown = Instance::new_simple("127.0.0.1", 9090, &service_name, "DEFAULT_GROUP");
+        let err = naming_client.update_instance(unknown).await.unwrap_err();
+        assert!(err.is_not_found(), "{:?}", err);
+        assert_eq!(server.get_instances(&key).len(), 1);
+
+        server.inject_failure(MockFailure::Error(500), Some(1));
+        instance.weight = 3f32;
+        assert!(naming_client.update_instance(instance).await.is_err());
+        assert_eq!(server.get_instances(&key)[0].weight, 2f32);
+
+        naming_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_update_instance);
+
+fn check_shutdown(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    let (config_client, naming_client) = build_clients(&server, use_grpc);
+    let service_name = format!("shutdown_service_{}", use_grpc);
+    let key = ServiceInstanceKey::new(&service_name, "DEFAULT_GROUP");
+    actix::System::new().block_on(async move {
+        let config_key = config_client.gene_config_key("shutdown_data", "");
+        server.publish_config(&config_key, "v1");
+        let _watch = config_client.watch(&config_key).await.unwrap();
+        naming_client
+            .register_async(Instance::new_simple("127.0.0.1", 8080, &service_name, ""))
+            .await
+            .unwrap();
+        assert_eq!(server.get_instances(&key).len(), 1);
+
+        naming_client.shutdown().await.unwrap();
+        assert!(server.get_instances(&key).is_empty());
+        //配置客户端仍在使用连接
+        let expected = if use_grpc { 1 } else { 0 };
+        assert_eq!(server.connection_count(), expected);
+
+        config_client.shutdown().await.unwrap();
+        for _ in 0..50 {
+            if server.connection_count() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(server.connection_count(), 0);
+    });
+}
+
+protocol_test!(check_shutdown);
+
+fn check_config_cas(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    let (config_client, _) = build_clients(&server, use_grpc);
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("cas_data", "");
+        server.publish_config(&key, "v1");
+        let value = config_client.get_config_value(&key).await.unwrap();
+        let result = config_client
+            .set_config_cas(&key, "v2", &value.md5)
+            .await
+            .unwrap();
+        assert_eq!(result, ConfigPublishResult::Published);
+        assert_eq!(server.get_config(&key).unwrap(), "v2");
+
+        //内容已被修改时返回冲突,不覆盖服务端内容
+        let result = config_client
+            .set_config_cas(&key, "v3", &value.md5)
+            .await
+            .unwrap();
+        assert_eq!(result, ConfigPublishResult::Conflict);
+        assert_eq!(server.get_config(&key).unwrap(), "v2");
+
+        config_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_config_cas);
+
+fn check_config_snapshot(use_grpc: bool) {
+    let dir = std::env::temp_dir().join(format!(
+        "nacos_snapshot_testkit_{}_{}",
+        use_grpc,
+        std::process::id()
+    ));
+    let server = MockNacosServer::start().unwrap();
+    let config_client = ClientBuilder::new()
+        .set_endpoint_addrs(&server.addr())
+        .set_use_grpc(use_grpc)
+        .set_config_snapshot_dir(Some(dir.clone()))
+        .build_config_client();
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("snapshot_data", "");
+        server.publish_config(&key, "v1");
+        let value = config_client.get_config_value(&key).await.unwrap();
+        assert!(!value.from_snapshot);
+
+        //服务端已响应的错误不使用快照
+        for code in [403, 500] {
+            server.inject_failure(MockFailure::Error(code), None);
+            let err = config_client.get_config_value(&key).await.unwrap_err();
+            assert!(err.is_server_reply(), "{:?}", err);
+            server.clear_failure();
+        }
+
+        //服务端不可用时使用快照
+        drop(server);
+        let value = config_client.get_config_value(&key).await.unwrap();
+        assert!(value.from_snapshot);
+        assert_eq!(value.content, "v1");
+        config_client.shutdown().await.ok();
+    });
+    std::fs::remove_dir_all(dir).ok();
+}
+
+protocol_test!(check_config_snapshot);
+
+fn check_persistent_instance(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    let (_, naming_client) = build_clients(&server, use_grpc);
+    let service_name = format!("persistent_service_{}", use_grpc);
+    let key = ServiceInstanceKey::new(&service_name, "DEFAULT_GROUP");
+    actix::System::new().block_on(async move {
+        let mut instance = Instance::new_simple("127.0.0.1", 8080, &service_name, "DEFAULT_GROUP");
+        instance.ephemeral = false;
+        naming_client
+            .register_async(instance.clone())
+            .await
+            .unwrap();
+        let instances = server.get_instances(&key);
+        assert_eq!(instances.len(), 1);
+        assert!(!instances[0].ephemeral);
+
+        naming_client.unregister_async(instance).await.unwrap();
+        assert!(server.get_instances(&key).is_empty());
+
+        naming_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_persistent_instance);
+
+fn check_register_error(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    let (_, naming_client) = build_clients(&server, use_grpc);
+    let service_name = format!("register_error_service_{}", use_grpc);
+    let key = ServiceInstanceKey::new(&service_name, "DEFAULT_GROUP");
+    actix::System::new().block_on(async move {
+        //等待连接建立后再注入故障
+        let instance = Instance::new_simple("127.0.0.1", 8080, &service_name, "DEFAULT_GROUP");
+        naming_client.register_async(instance).await.unwrap();
+
+        server.inject_failure(MockFailure::Error(500), Some(1));
+        let failed = Instance::new_simple("127.0.0.1", 9090, &service_name, "DEFAULT_GROUP");
+        let err = naming_client.register_async(failed).await.unwrap_err();
+        assert!(err.is_server_reply(), "{:?}", err);
+        assert_eq!(server.get_instances(&key).len(), 1);
+
+        naming_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_register_error);
+
+#[test]
+fn test_address_server() {
+    let server_a = MockNacosServer::start().unwrap();
+    let server_b = MockNacosServer::start().unwrap();
+    let address_server = AddressServer::new(&server_a.address_server_url())
+        .set_refresh_interval(Duration::from_millis(200));
+    let config_client = ClientBuilder::new()
+        .set_address_server(address_server)
+        .build_config_client();
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("address_server_data", "");
+        server_a.publish_config(&key, "a");
+        server_b.publish_config(&key, "b");
+        assert!(wait_config(&config_client, &key, "a").await);
+
+        //集群扩缩容后切换到新的地址
+        server_a.set_server_list(vec![server_b.addr()]);
+        assert!(wait_config(&config_client, &key, "b").await);
+
+        config_client.shutdown().await.ok();
+    });
+}
+
+#[test]
+fn test_connect_reset() {
+    let server_a = MockNacosServer::start().unwrap();
+    let server_b = MockNacosServer::start().unwrap();
+    let config_client = ClientBuilder::new()
+        .set_endpoint_addrs(&format!("{},{}", server_a.addr(), server_b.addr()))
+        .build_config_client();
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("connect_reset_data", "");
+        server_a.publish_config(&key, "a");
+        server_b.publish_config(&key, "b");
+        let (current, other, value) = loop {
+            if server_a.connection_count() > 0 {
+                break (&server_a, &server_b, "b");
+            }
+            if server_b.connection_count() > 0 {
+                break (&server_b, &server_a, "a");
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+
+        //服务端要求切换到指定地址
+        current.reset_connections(Some(&other.host()));
+        assert!(wait_config(&config_client, &key, value).await);
+        assert_eq!(current.connection_count(), 0);
+
+        config_client.shutdown().await.ok();
+    });
+}
+
+fn check_connection_policy(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    let policy = ConnectionPolicy::new()
+        .set_max_retries(2)
+        .set_operation_timeout(RequestOperation::GetConfig, Duration::from_millis(500));
+    let config_client = ClientBuilder::new()
+        .set_endpoint_addrs(&server.addr())
+        .set_use_grpc(use_grpc)
+        .set_connection_policy(policy)
+        .build_config_client();
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("policy_data", "");
+        server.publish_config(&key, "v1");
+        assert!(wait_config(&config_client, &key, "v1").await);
+
+        //网络错误时自动重试
+        server.inject_failure(MockFailure::Disconnect, Some(2));
+        assert_eq!(config_client.get_config(&key).await.unwrap(), "v1");
+
+        //超过重试次数后返回超时错误
+        server.inject_failure(MockFailure::Delay(Duration::from_secs(1)), None);
+        let err = config_client.get_config(&key).await.unwrap_err();
+        assert!(
+            matches!(err, nacos_rust_client::Error::Timeout(_)),
+            "{:?}",
+            err
+        );
+        server.clear_failure();
+
+        config_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_connection_policy);
+
+fn check_access_key(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    server.set_access_key("test_ak", "test_sk");
+    let (config_client, naming_client) = ClientBuilder::new()
+        .set_endpoint_addrs(&server.addr())
+        .set_use_grpc(use_grpc)
+        .set_auth_provider(Arc::new(AccessKeyAuthProvider::new("test_ak", "test_sk")))
+        .build();
+    let service_name = format!("access_key_service_{}", use_grpc);
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("access_key_data", "");
+        config_client.set_config(&key, "v1").await.unwrap();
+        assert!(wait_config(&config_client, &key, "v1").await);
+
+        naming_client
+            .register_async(Instance::new_simple("127.0.0.1", 8080, &service_name, ""))
+            .await
+            .unwrap();
+        assert!(wait_instance(&naming_client, &service_name, 8080).await);
+
+        config_client.shutdown().await.ok();
+        naming_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_access_key);
+
+fn build_auth_client(
+    server: &MockNacosServer,
+    use_grpc: bool,
+    password: &str,
+) -> (Arc<ConfigClient>, Arc<Mutex<Vec<AuthState>>>) {
+    let states = Arc::new(Mutex::new(Vec::new()));
+    let listener_states = states.clone();
+    let (config_client, _) = ClientBuilder::new()
+        .set_endpoint_addrs(&server.addr())
+        .set_use_grpc(use_grpc)
+        .set_auth_info(Some(AuthInfo::new("nacos", password)))
+        .set_auth_state_listener(move |state| listener_states.lock().unwrap().push(state))
+        .build();
+    (config_client, states)
+}
+
+async fn wait_auth_state(states: &Mutex<Vec<AuthState>>, f: impl Fn(&AuthState) -> bool) -> bool {
+    for _ in 0..50 {
+        if states.lock().unwrap().iter().any(&f) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+fn check_auth_state(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    server.set_credentials("nacos", "nacos");
+    let (config_client, states) = build_auth_client(&server, use_grpc, "nacos");
+    let (failed_client, failed_states) = build_auth_client(&server, use_grpc, "wrong");
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("auth_state_data", "");
+        config_client.set_config(&key, "v1").await.unwrap();
+        assert!(wait_auth_state(&states, |e| e == &AuthState::Authenticated).await);
+
+        //token 被服务端拒绝后重新登录并重试
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        server.expire_tokens();
+        config_client.set_config(&key, "v2").await.unwrap();
+        assert_eq!(server.get_config(&key).unwrap(), "v2");
+        assert!(states.lock().unwrap().contains(&AuthState::Expired));
+
+        //登录失败时不发送未鉴权的请求
+        assert!(wait_auth_state(&failed_states, |e| matches!(e, AuthState::Failed(_))).await);
+        let err = failed_client.set_config(&key, "v3").await.unwrap_err();
+        assert!(err.is_unauthorized());
+        assert_eq!(server.get_config(&key).unwrap(), "v2");
+
+        config_client.shutdown().await.ok();
+        failed_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_auth_state);
+
+#[test]
+fn test_access_token_header() {
+    let server = MockNacosServer::start().unwrap();
+    server.set_credentials("nacos", "nacos");
+    server.require_header_token();
+    let build = |position| {
+        ClientBuilder::new()
+            .set_endpoint_addrs(&server.addr())
+            .set_use_grpc(false)
+            .set_auth_info(Some(AuthInfo::new("nacos", "nacos")))
+            .set_access_token_position(position)
+            .build()
+    };
+    let (config_client, naming_client) = build(AccessTokenPosition::Header);
+    let (bearer_client, _) = build(AccessTokenPosition::Authorization);
+    let (query_client, _) = build(AccessTokenPosition::Query);
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("token_header_data", "");
+        config_client.set_config(&key, "v1").await.unwrap();
+        assert!(wait_config(&config_client, &key, "v1").await);
+        bearer_client.set_config(&key, "v2").await.unwrap();
+        assert_eq!(server.get_config(&key).unwrap(), "v2");
+
+        naming_client
+            .register_async(Instance::new_simple(
+                "127.0.0.1",
+                8080,
+                "token_header_service",
+                "",
+            ))
+            .await
+            .unwrap();
+        assert!(wait_instance(&naming_client, "token_header_service", 8080).await);
+
+        //默认放在 url 中的 token 会被拒绝
+        let err = query_client.set_config(&key, "v3").await.unwrap_err();
+        assert!(err.is_unauthorized());
+        assert_eq!(server.get_config(&key).unwrap(), "v2");
+
+        config_client.shutdown().await.ok();
+        bearer_client.shutdown().await.ok();
+        query_client.shutdown().await.ok();
+        naming_client.shutdown().await.ok();
+    });
+}
+
+#[test]
+fn test_file_auth_provider_rotation() {
+    let dir = std::env::temp_dir().join(format!("nacos_secret_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("username"), "nacos").unwrap();
+    std::fs::write(dir.join("password"), "v1").unwrap();
+    let server = MockNacosServer::start().unwrap();
+    server.set_credentials("nacos", "v1");
+    let (config_client, _) = ClientBuilder::new()
+        .set_endpoint_addrs(&server.addr())
+        .set_use_grpc(false)
+        .set_auth_provider(Arc::new(FileAuthProvider::new(&dir)))
+        .build();
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("file_auth_data", "");
+        config_client.set_config(&key, "v1").await.unwrap();
+
+        //secret 轮换后,token 失效时使用新密码重新登录
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        std::fs::write(dir.join("password"), "rotated").unwrap();
+        server.set_credentials("nacos", "rotated");
+        server.expire_tokens();
+        config_client.set_config(&key, "v2").await.unwrap();
+        assert_eq!(server.get_config(&key).unwrap(), "v2");
+
+        config_client.shutdown().await.ok();
+        std::fs::remove_dir_all(&dir).ok();
+    });
+}
+
+#[test]
+fn test_file_auth_provider_empty_dir() {
+    let dir = std::env::temp_dir().join(format!("nacos_secret_empty_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let server = MockNacosServer::start().unwrap();
+    server.set_credentials("nacos", "nacos");
+    let (config_client, _) = ClientBuilder::new()
+        .set_endpoint_addrs(&server.addr())
+        .set_use_grpc(false)
+        .set_auth_provider(Arc::new(FileAuthProvider::new(&dir)))
+        .build();
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("file_auth_empty_data", "");
+        let err = config_client.set_config(&key, "v1").await.unwrap_err();
+        assert!(err.is_unauthorized(), "{:?}", err);
+
+        //secret 在客户端启动后才挂载
+        std::fs::write(dir.join("username"), "nacos").unwrap();
+        std::fs::write(dir.join("password"), "nacos").unwrap();
+        config_client.set_config(&key, "v1").await.unwrap();
+        assert_eq!(server.get_config(&key).unwrap(), "v1");
+
+        config_client.shutdown().await.ok();
+        std::fs::remove_dir_all(&dir).ok();
+    });
+}
+
+fn check_config_watch(use_grpc: bool) {
+    let server = MockNacosServer::start().unwrap();
+    let (config_client, _) = build_clients(&server, use_grpc);
+    actix::System::new().block_on(async move {
+        let key = config_client.gene_config_key("watch_data", "");
+        server.publish_config(&key, "v1");
+        let mut changes = Box::pin(config_client.changes());
+        let mut rx = config_client.watch(&key).await.unwrap();
+        assert_eq!(rx.borrow_and_update().as_deref().unwrap(), "v1");
+
+        server.publish_config(&key, "v2");
+        tokio::time::timeout(Duration::from_secs(5), rx.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rx.borrow().as_deref().unwrap(), "v2");
+        let event = tokio::time::timeout(Duration::from_secs(5), changes.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.key.data_id, key.data_id);
+        assert_eq!(event.content.as_deref().unwrap(), "v2");
+
+        //配置被删除后值为 None
+        server.remove_config(&key);
+        tokio::time::timeout(Duration::from_secs(5), rx.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(rx.borrow().is_none());
+        let event = tokio::time::timeout(Duration::from_secs(5), changes.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.key.data_id, key.data_id);
+        assert!(event.content.is_none());
+        assert!(event.md5.is_empty());
+
+        config_client.shutdown().await.ok();
+    });
+}
+
+protocol_test!(check_config_watch);
+
+#[test]
+fn test_protect_threshold_grpc() {
+    let server = MockNacosServer::start().unwrap();
+    let key = ServiceInstanceKey::new("protect_service", "DEFAULT_GROUP");
+    let instances = (0..4)
+        .map(|i| {
+            let mut instance =
+                Instance::new_simple("127.0.0.1", 8000 + i, "protect_service", "DEFAULT_GROUP");
+            instance.healthy = i == 0;
+            instance
+        })
+        .collect();
+    server.set_instances(&key, instances);
+    server.set_protect_threshold(&key, 0.5);
+    let build = |use_protect_threshold| {
+        ClientBuilder::new()
+            .set_endpoint_addrs(&server.addr())
+            .set_use_grpc(true)
+            .set_naming_protect_policy(NamingProtectPolicy {
+                use_protect_threshold,
+                ..Default::default()
+            })
+            .build_naming_client()
+    };
+    let protect_client = build(true);
+    let naming_client = build(false);
+    actix::System::new().block_on(async move {
+        let query = |client: Arc<NamingClient>| async move {
+            for _ in 0..50 {
+                let params =
+                    QueryInstanceListParams::new_simple("protect_service", "DEFAULT_GROUP");
+                if let Ok(list) = client.query_instances(params).await {
+                    if !list.is_empty() {
+                        return list.len();
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            0
+        };
+        //服务端返回 reachProtectionThreshold 时,只查健康实例也返回全部实例
+        assert_eq!(query(protect_client.clone()).await, 4);
+        assert_eq!(query(naming_client.clone()).await, 1);
+
+        protect_client.shutdown().await.ok();
+        naming_client.shutdown().await.ok();
+    });
+}